@@ -0,0 +1,258 @@
+use crate::{
+    AudioFile, SonicTunesError, audiofile_to_url, get_cache_dir_path, get_download_client,
+};
+use rand::random_range;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+struct AudioCacheEntry {
+    audiofile: AudioFile,
+    size: u64,
+    last_used: u64,
+    pinned: bool,
+}
+
+#[derive(Default)]
+struct AudioCacheState {
+    entries: std::collections::HashMap<String, AudioCacheEntry>,
+    pending: std::collections::HashSet<String>,
+    // Set when only the LRU order changed, which is saved with the next download or on quit.
+    unsaved: bool,
+}
+
+struct DownloadRequest {
+    url: String,
+    audiofile: AudioFile,
+    pinned: bool,
+}
+
+#[derive(Clone)]
+pub struct AudioCache {
+    dir_path: Option<String>,
+    max_size: u64,
+    cache_played: bool,
+    state: std::sync::Arc<std::sync::Mutex<AudioCacheState>>,
+    download_s: crossbeam::channel::Sender<DownloadRequest>,
+}
+
+impl AudioCache {
+    pub fn new(max_size: u64, cache_played: bool) -> Self {
+        let dir_path = get_cache_dir_path().map(|dir_path| format!("{dir_path}/audio"));
+        let mut state = AudioCacheState::default();
+        if let Some(ref dir_path) = dir_path {
+            state.entries = load_index(dir_path)
+                .into_iter()
+                .filter(|entry| {
+                    std::path::PathBuf::from(file_path(dir_path, &entry.audiofile.id)).is_file()
+                })
+                .map(|entry| (entry.audiofile.id.clone(), entry))
+                .collect();
+            log::debug!("AudioCache: loaded {} files", state.entries.len());
+        }
+
+        let (download_s, download_r) = crossbeam::channel::unbounded();
+        let audio_cache = AudioCache {
+            dir_path,
+            max_size,
+            cache_played,
+            state: std::sync::Arc::new(std::sync::Mutex::new(state)),
+            download_s,
+        };
+
+        let worker = audio_cache.clone();
+        std::thread::spawn(move || {
+            for request in download_r.iter() {
+                let id = request.audiofile.id.clone();
+                if let Err(err) = worker.fetch(request) {
+                    log::error!("AudioCache: {id}: {err:?}");
+                }
+                worker.state.lock().unwrap().pending.remove(&id);
+            }
+        });
+
+        audio_cache
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.state.lock().unwrap().entries.is_empty()
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.state.lock().unwrap().entries.contains_key(id)
+    }
+
+    pub fn path(&self, audiofile: &AudioFile) -> Option<String> {
+        let dir_path = self.dir_path.as_ref()?;
+        let path = file_path(dir_path, &audiofile.id);
+        let mut state = self.state.lock().unwrap();
+        if !std::path::PathBuf::from(&path).is_file() {
+            state.entries.remove(&audiofile.id);
+            return None;
+        }
+        state.entries.get_mut(&audiofile.id)?.last_used = unix_timestamp();
+        state.unsaved = true;
+
+        Some(path)
+    }
+
+    pub fn save(&self) {
+        let Some(ref dir_path) = self.dir_path else {
+            return;
+        };
+        let mut state = self.state.lock().unwrap();
+        if !state.unsaved {
+            return;
+        }
+        match save_index(dir_path, &state.entries) {
+            Ok(()) => state.unsaved = false,
+            Err(err) => log::error!("AudioCache: {err:?}"),
+        }
+    }
+
+    pub fn random_audiofile(&self) -> Option<AudioFile> {
+        let state = self.state.lock().unwrap();
+        if state.entries.is_empty() {
+            return None;
+        }
+        let i = random_range(0..state.entries.len());
+
+        state
+            .entries
+            .values()
+            .nth(i)
+            .map(|entry| entry.audiofile.clone())
+    }
+
+    pub fn played(&self, url: &str, audiofile: &AudioFile) {
+        if self.cache_played {
+            self.download(url, vec![audiofile.clone()], false);
+        }
+    }
+
+    pub fn download(&self, url: &str, audiofiles: Vec<AudioFile>, pinned: bool) {
+        let Some(ref dir_path) = self.dir_path else {
+            return;
+        };
+        let mut state = self.state.lock().unwrap();
+        let mut pinned_cached = false;
+        for audiofile in audiofiles {
+            if let Some(entry) = state.entries.get_mut(&audiofile.id) {
+                pinned_cached |= pinned && !entry.pinned;
+                entry.pinned |= pinned;
+            } else if state.pending.insert(audiofile.id.clone()) {
+                let _ = self.download_s.send(DownloadRequest {
+                    url: url.to_string(),
+                    audiofile,
+                    pinned,
+                });
+            }
+        }
+        if pinned_cached {
+            match save_index(dir_path, &state.entries) {
+                Ok(()) => state.unsaved = false,
+                Err(err) => log::error!("AudioCache: {err:?}"),
+            }
+        }
+    }
+
+    fn fetch(&self, request: DownloadRequest) -> Result<(), SonicTunesError> {
+        let Some(ref dir_path) = self.dir_path else {
+            return Ok(());
+        };
+        if !request.pinned && self.max_size == 0 {
+            return Ok(());
+        }
+        std::fs::create_dir_all(dir_path)?;
+        let path = file_path(dir_path, &request.audiofile.id);
+        let part_path = format!("{path}.part");
+
+        let mut response = get_download_client()?
+            .get(audiofile_to_url(&request.url, &request.audiofile))
+            .send()?
+            .error_for_status()?;
+        let mut part_file = std::fs::File::create(&part_path)?;
+        let size = match response.copy_to(&mut part_file) {
+            Ok(size) => size,
+            Err(err) => {
+                let _ = std::fs::remove_file(&part_path);
+                return Err(err.into());
+            }
+        };
+        std::fs::rename(&part_path, &path)?;
+        log::debug!(
+            "AudioCache: downloaded {} ({size} bytes)",
+            request.audiofile.path
+        );
+
+        let mut state = self.state.lock().unwrap();
+        state.entries.insert(
+            request.audiofile.id.clone(),
+            AudioCacheEntry {
+                audiofile: request.audiofile,
+                size,
+                last_used: unix_timestamp(),
+                pinned: request.pinned,
+            },
+        );
+        self.evict(dir_path, &mut state.entries);
+        save_index(dir_path, &state.entries)?;
+        state.unsaved = false;
+
+        Ok(())
+    }
+
+    fn evict(
+        &self,
+        dir_path: &str,
+        entries: &mut std::collections::HashMap<String, AudioCacheEntry>,
+    ) {
+        let mut total_size: u64 = entries.values().map(|entry| entry.size).sum();
+        let mut unpinned: Vec<(String, u64, u64)> = entries
+            .values()
+            .filter(|entry| !entry.pinned)
+            .map(|entry| (entry.audiofile.id.clone(), entry.size, entry.last_used))
+            .collect();
+        unpinned.sort_unstable_by_key(|&(_, _, last_used)| last_used);
+
+        for (id, size, _) in unpinned {
+            if total_size <= self.max_size {
+                break;
+            }
+            if let Err(err) = std::fs::remove_file(file_path(dir_path, &id)) {
+                log::error!("AudioCache: {err:?}");
+            }
+            entries.remove(&id);
+            total_size -= size;
+        }
+    }
+}
+
+fn file_path(dir_path: &str, id: &str) -> String {
+    let file_name: String = id.bytes().map(|b| format!("{b:02x}")).collect();
+
+    format!("{dir_path}/{file_name}")
+}
+
+fn unix_timestamp() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+fn load_index(dir_path: &str) -> Vec<AudioCacheEntry> {
+    std::fs::read_to_string(format!("{dir_path}/index.json"))
+        .ok()
+        .and_then(|index| serde_json::from_str(&index).ok())
+        .unwrap_or_default()
+}
+
+fn save_index(
+    dir_path: &str,
+    entries: &std::collections::HashMap<String, AudioCacheEntry>,
+) -> Result<(), SonicTunesError> {
+    std::fs::create_dir_all(dir_path)?;
+    let index = serde_json::to_string(&entries.values().collect::<Vec<_>>())?;
+    std::fs::write(format!("{dir_path}/index.json"), index)?;
+
+    Ok(())
+}
@@ -0,0 +1,205 @@
+use crate::libmpv_handler::RepeatMode;
+use crate::shuffle::ShuffleMode;
+use crate::tui::cover_art::AlbumArtMode;
+use crate::{ProgramOption, SonicTunesError, get_config_dir_path};
+
+#[derive(serde::Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub url: Option<String>,
+    pub urls: Vec<String>,
+    pub volume: i64,
+    pub shuffle_mode: ShuffleMode,
+    pub repeat_mode: RepeatMode,
+    pub theme: Option<String>,
+    pub themes: std::collections::BTreeMap<String, ThemeConfig>,
+    pub album_art: AlbumArtMode,
+    pub keybindings: std::collections::BTreeMap<String, String>,
+    pub verbose: bool,
+    pub log_path: String,
+    pub timeout: u64,
+    pub cache: CacheConfig,
+}
+
+#[derive(serde::Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    pub enabled: bool,
+    pub refresh_interval: Option<u64>,
+    pub audio: bool,
+    pub audio_max_size: u64,
+}
+
+#[derive(serde::Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThemeConfig {
+    pub base: Option<String>,
+    pub border: Option<String>,
+    pub title: Option<String>,
+    pub text: Option<String>,
+    pub dim: Option<String>,
+    pub highlight: Option<String>,
+    pub current: Option<String>,
+    pub progress: Option<String>,
+    pub volume: Option<String>,
+    pub command: Option<String>,
+    pub error: Option<String>,
+    pub timer: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            url: None,
+            urls: vec![],
+            volume: 50,
+            shuffle_mode: ShuffleMode::Bag,
+            repeat_mode: RepeatMode::Off,
+            theme: None,
+            themes: std::collections::BTreeMap::new(),
+            album_art: AlbumArtMode::default(),
+            keybindings: std::collections::BTreeMap::new(),
+            verbose: false,
+            log_path: "debug.log".to_string(),
+            timeout: 1,
+            cache: CacheConfig::default(),
+        }
+    }
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            enabled: true,
+            refresh_interval: None,
+            audio: false,
+            audio_max_size: 1024,
+        }
+    }
+}
+
+impl Config {
+    pub fn load(path: Option<&str>) -> Result<Self, SonicTunesError> {
+        let (path, required) = match path {
+            Some(path) => (path.to_string(), true),
+            None => match get_config_file_path() {
+                Some(path) => (path, false),
+                None => return Ok(Config::default()),
+            },
+        };
+
+        if !required && !std::path::PathBuf::from(&path).is_file() {
+            return Ok(Config {
+                url: load_legacy_url(),
+                ..Default::default()
+            });
+        }
+
+        let config_str = std::fs::read_to_string(&path)
+            .map_err(|err| SonicTunesError::ConfigError(format!("{path}: {err}")))?;
+        let config: Config = toml::from_str(&config_str)
+            .map_err(|err| SonicTunesError::ConfigError(format!("{path}: {err}")))?;
+        if !(0..=100).contains(&config.volume) {
+            return Err(SonicTunesError::ConfigError(format!(
+                "{path}: volume must be within 0..100"
+            )));
+        }
+        if config.timeout == 0 {
+            return Err(SonicTunesError::ConfigError(format!(
+                "{path}: timeout must be at least 1 second"
+            )));
+        }
+
+        Ok(config)
+    }
+
+    pub fn apply_options(&mut self, options: &[ProgramOption]) {
+        for option in options {
+            match option {
+                ProgramOption::URL(url) => {
+                    self.url = Some(url.clone());
+                    self.urls.clear();
+                }
+                ProgramOption::Volume(vol) => self.volume = *vol,
+                ProgramOption::Verbose => self.verbose = true,
+                ProgramOption::NoCache => self.cache.enabled = false,
+                ProgramOption::RefreshInterval(min) => self.cache.refresh_interval = Some(*min),
+                ProgramOption::PrintHelp
+                | ProgramOption::ConfigPath(_)
+                | ProgramOption::Remote(_)
+                | ProgramOption::StatusFormat(_)
+                | ProgramOption::StatusJson
+                | ProgramOption::Daemon
+                | ProgramOption::NoTui => {}
+            }
+        }
+    }
+
+    pub fn server_urls(&self) -> Vec<String> {
+        self.url.iter().chain(self.urls.iter()).cloned().collect()
+    }
+}
+
+fn get_config_file_path() -> Option<String> {
+    get_config_dir_path().map(|dir_path| format!("{dir_path}/config.toml"))
+}
+
+fn load_legacy_url() -> Option<String> {
+    let path = format!("{}/config", get_config_dir_path()?);
+    let url = std::fs::read_to_string(path).ok()?;
+
+    Some(url.trim().to_string()).filter(|url| url.starts_with("http"))
+}
+
+// Sets `url` unless the URL is already configured, editing the file line by line to keep comments.
+// Writes to the config file that was loaded, the one given with --config included.
+pub fn save_url_to_config(config_path: Option<&str>, url: &str) -> Result<(), SonicTunesError> {
+    let Some(path) = config_path
+        .map(str::to_string)
+        .or_else(get_config_file_path)
+    else {
+        return Ok(());
+    };
+    let url_line = format!("url = {}", toml::Value::String(url.to_string()));
+    let config_str = match std::fs::read_to_string(&path) {
+        Ok(config_str) => config_str,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            if let Some(dir_path) = std::path::Path::new(&path).parent() {
+                std::fs::create_dir_all(dir_path)?;
+            }
+            std::fs::write(&path, format!("{url_line}\n"))?;
+            return Ok(());
+        }
+        Err(err) => return Err(err.into()),
+    };
+    let config: Config = toml::from_str(&config_str)
+        .map_err(|err| SonicTunesError::ConfigError(format!("{path}: {err}")))?;
+    if config
+        .server_urls()
+        .iter()
+        .any(|server_url| server_url == url)
+    {
+        return Ok(());
+    }
+
+    let mut lines: Vec<&str> = config_str.lines().collect();
+    // Top-level keys end at the first table header.
+    let top_level = lines
+        .iter()
+        .position(|line| line.trim_start().starts_with('['))
+        .unwrap_or(lines.len());
+    let url_index = lines[..top_level].iter().position(|line| {
+        line.trim_start()
+            .strip_prefix("url")
+            .is_some_and(|rest| rest.trim_start().starts_with('='))
+    });
+    match url_index {
+        Some(i) => lines[i] = &url_line,
+        None => lines.insert(0, &url_line),
+    }
+    let mut config_str = lines.join("\n");
+    config_str.push('\n');
+    std::fs::write(&path, config_str)?;
+
+    Ok(())
+}
@@ -0,0 +1,145 @@
+use crate::{AudioFile, SonicTunesError, get_config_dir_path};
+use std::io::Write;
+
+const MAX_ENTRIES: usize = 1000;
+// The file is trimmed back to MAX_ENTRIES lines once it grows past this size.
+const MAX_FILE_SIZE: u64 = 1024 * 1024;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct HistoryEntry {
+    pub timestamp: i64,
+    pub id: String,
+    pub path: String,
+    pub mime: String,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub listened: f64,
+    pub skipped: bool,
+}
+
+impl HistoryEntry {
+    pub fn new(
+        audiofile: &AudioFile,
+        title: &str,
+        artist: Option<&str>,
+        album: Option<&str>,
+    ) -> Self {
+        HistoryEntry {
+            timestamp: chrono::Utc::now().timestamp(),
+            id: audiofile.id.clone(),
+            path: audiofile.path.clone(),
+            mime: audiofile.mime.clone(),
+            title: Some(title.to_string()),
+            artist: artist.map(|s| s.to_string()),
+            album: album.map(|s| s.to_string()),
+            listened: 0.0,
+            skipped: false,
+        }
+    }
+
+    pub fn to_audiofile(&self) -> AudioFile {
+        AudioFile {
+            id: self.id.clone(),
+            path: self.path.clone(),
+            mime: self.mime.clone(),
+            title: self.title.clone(),
+            artist: self.artist.clone(),
+            album: self.album.clone(),
+        }
+    }
+
+    pub fn generate_str(&self) -> String {
+        let mut entry_str = chrono::DateTime::from_timestamp(self.timestamp, 0)
+            .map(|timestamp| {
+                timestamp
+                    .with_timezone(&chrono::Local)
+                    .format("%Y-%m-%d %H:%M ")
+                    .to_string()
+            })
+            .unwrap_or_default();
+        entry_str.push_str(self.title.as_deref().unwrap_or(&self.path));
+        if let Some(ref artist) = self.artist {
+            entry_str.push_str(" by ");
+            entry_str.push_str(artist);
+        }
+        if self.skipped {
+            entry_str.push_str(" (skipped)");
+        }
+
+        entry_str
+    }
+}
+
+fn get_history_file_path() -> Option<String> {
+    get_config_dir_path().map(|dir_path| format!("{dir_path}/history"))
+}
+
+pub fn load_history() -> Vec<HistoryEntry> {
+    let Some(history) = get_history_file_path().and_then(|path| std::fs::read_to_string(path).ok())
+    else {
+        return vec![];
+    };
+    let mut entries: Vec<HistoryEntry> = history
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect();
+    if entries.len() > MAX_ENTRIES {
+        entries.drain(..entries.len() - MAX_ENTRIES);
+    }
+
+    entries
+}
+
+pub fn append_to_history(entry: &HistoryEntry) -> Result<(), SonicTunesError> {
+    let (Some(dir_path), Some(path)) = (get_config_dir_path(), get_history_file_path()) else {
+        return Ok(());
+    };
+    std::fs::create_dir_all(dir_path)?;
+    let mut history_file = std::fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(&path)?;
+    writeln!(history_file, "{}", serde_json::to_string(entry)?)?;
+    if history_file.metadata()?.len() > MAX_FILE_SIZE {
+        trim_history(&path)?;
+    }
+
+    Ok(())
+}
+
+fn trim_history(path: &str) -> Result<(), SonicTunesError> {
+    let history = std::fs::read_to_string(path)?;
+    let lines: Vec<&str> = history.lines().collect();
+    let mut new_history = lines[lines.len().saturating_sub(MAX_ENTRIES)..].join("\n");
+    new_history.push('\n');
+    let tmp_path = format!("{path}.tmp");
+    std::fs::write(&tmp_path, new_history)?;
+    std::fs::rename(tmp_path, path)?;
+
+    Ok(())
+}
+
+pub fn remove_from_history(entry: &HistoryEntry) -> Result<(), SonicTunesError> {
+    let Some(path) = get_history_file_path() else {
+        return Ok(());
+    };
+    let history = std::fs::read_to_string(&path)?;
+    let mut removed = false;
+    let mut new_history = String::new();
+    for line in history.lines() {
+        if !removed
+            && let Ok(line_entry) = serde_json::from_str::<HistoryEntry>(line)
+            && line_entry.timestamp == entry.timestamp
+            && line_entry.id == entry.id
+        {
+            removed = true;
+            continue;
+        }
+        new_history.push_str(line);
+        new_history.push('\n');
+    }
+    std::fs::write(path, new_history)?;
+
+    Ok(())
+}
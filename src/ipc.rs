@@ -0,0 +1,398 @@
+use crate::libmpv_handler::{LibMpvEventMessage, LibMpvMessage, PlayerStatus};
+use crate::tui::commands::{TuiCommand, map_str_to_tuicommand};
+use crate::{SonicTunesError, get_runtime_dir_path, library_cache::LibraryCache};
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct AttachInfo {
+    pub url: String,
+}
+
+pub fn get_socket_path() -> Option<String> {
+    get_runtime_dir_path().map(|dir_path| format!("{dir_path}/socket"))
+}
+
+pub fn handle_command(
+    command_str: &str,
+    libmpv_s: &crossbeam::channel::Sender<LibMpvMessage>,
+) -> Result<String, SonicTunesError> {
+    match command_str.trim() {
+        "status" => return Ok(request_status(libmpv_s)?.generate_str()),
+        "status-json" => return Ok(serde_json::to_string(&request_status(libmpv_s)?)? + "\n"),
+        _ => (),
+    }
+
+    let Some(command) = map_str_to_tuicommand(command_str) else {
+        return Ok("error: unknown command\n".to_string());
+    };
+    let Some(msg) = tuicommand_to_libmpv_message(command) else {
+        return Ok("error: command not available remotely\n".to_string());
+    };
+    libmpv_s.send(msg)?;
+
+    Ok("ok\n".to_string())
+}
+
+fn request_status(
+    libmpv_s: &crossbeam::channel::Sender<LibMpvMessage>,
+) -> Result<PlayerStatus, SonicTunesError> {
+    let (status_s, status_r) = crossbeam::channel::bounded(1);
+    libmpv_s.send(LibMpvMessage::Status(status_s))?;
+
+    status_r
+        .recv_timeout(std::time::Duration::from_secs(1))
+        .map_err(|_| SonicTunesError::IPCError("no status received".to_string()))
+}
+
+fn tuicommand_to_libmpv_message(command: TuiCommand) -> Option<LibMpvMessage> {
+    match command {
+        TuiCommand::Quit => Some(LibMpvMessage::Quit),
+        TuiCommand::Volume(vol) => Some(LibMpvMessage::UpdateVolume(vol)),
+        TuiCommand::SetVolume(vol) => Some(LibMpvMessage::SetVolume(vol)),
+        TuiCommand::Seek(offset) => Some(LibMpvMessage::UpdatePosition(offset)),
+        TuiCommand::SetPosition(pos) => Some(LibMpvMessage::SetPosition(pos)),
+        TuiCommand::PlayPause => Some(LibMpvMessage::PlayPause),
+        TuiCommand::PlayNext => Some(LibMpvMessage::PlayNext),
+        TuiCommand::PlayPrevious => Some(LibMpvMessage::PlayPrevious),
+        TuiCommand::Stop => Some(LibMpvMessage::Stop),
+        TuiCommand::ClearQueue => Some(LibMpvMessage::ClearQueue),
+        TuiCommand::ShuffleMode(mode) => Some(LibMpvMessage::SetShuffleMode(mode)),
+        TuiCommand::RepeatMode(Some(mode)) => Some(LibMpvMessage::SetRepeatMode(mode)),
+        TuiCommand::RepeatMode(None) => Some(LibMpvMessage::CycleRepeatMode),
+        TuiCommand::PauseAfter(min) => Some(LibMpvMessage::PauseAfter(min)),
+        TuiCommand::QuitAfter(min) => Some(LibMpvMessage::QuitAfter(min)),
+        TuiCommand::Refresh => Some(LibMpvMessage::RefreshLibrary),
+        _ => None,
+    }
+}
+
+// Events buffered per attached client before it is considered stalled and dropped.
+#[cfg(unix)]
+const SUBSCRIBER_BUFFER: usize = 256;
+
+#[cfg(unix)]
+#[derive(Clone, Default)]
+pub struct EventSubscribers {
+    senders: std::sync::Arc<std::sync::Mutex<Vec<crossbeam::channel::Sender<String>>>>,
+}
+
+#[cfg(unix)]
+impl EventSubscribers {
+    // Each client gets its own writer, which sends the snapshot before any broadcast event.
+    fn spawn_writer(
+        mut stream: std::os::unix::net::UnixStream,
+        snapshot_r: crossbeam::channel::Receiver<LibMpvEventMessage>,
+    ) -> crossbeam::channel::Sender<String> {
+        use std::io::Write;
+
+        let (event_s, event_r) = crossbeam::channel::bounded::<String>(SUBSCRIBER_BUFFER);
+        std::thread::spawn(move || {
+            let snapshot = snapshot_r
+                .iter()
+                .filter_map(|msg| serde_json::to_string(&msg).ok());
+            for msg in snapshot.chain(event_r.iter()) {
+                if let Err(err) = writeln!(stream, "{msg}") {
+                    log::debug!("EventSubscribers: {err:?}");
+                    break;
+                }
+            }
+            let _ = stream.shutdown(std::net::Shutdown::Both);
+        });
+
+        event_s
+    }
+
+    pub fn subscribe(&self, event_s: crossbeam::channel::Sender<String>) {
+        self.senders.lock().unwrap().push(event_s);
+    }
+
+    pub fn broadcast(&self, msg: &LibMpvEventMessage) {
+        let mut senders = self.senders.lock().unwrap();
+        if senders.is_empty() {
+            return;
+        }
+        let msg = match serde_json::to_string(msg) {
+            Ok(msg) => msg,
+            Err(err) => {
+                log::error!("EventSubscribers: {err:?}");
+                return;
+            }
+        };
+        senders.retain(|event_s| match event_s.try_send(msg.clone()) {
+            Ok(()) => true,
+            Err(crossbeam::channel::TrySendError::Full(_)) => {
+                log::error!("EventSubscribers: client stalled, detaching it");
+                false
+            }
+            Err(crossbeam::channel::TrySendError::Disconnected(_)) => false,
+        });
+    }
+}
+
+#[cfg(unix)]
+pub struct IPCServer {
+    listener: std::os::unix::net::UnixListener,
+    url: String,
+    subscribers: EventSubscribers,
+}
+
+#[cfg(unix)]
+impl IPCServer {
+    pub fn bind(url: &str) -> Result<Self, SonicTunesError> {
+        let (Some(dir_path), Some(path)) = (get_runtime_dir_path(), get_socket_path()) else {
+            return Err(SonicTunesError::IPCError(
+                "no runtime directory available".to_string(),
+            ));
+        };
+        if std::os::unix::net::UnixStream::connect(&path).is_ok() {
+            return Err(SonicTunesError::IPCError(format!(
+                "{path} is in use by another instance"
+            )));
+        }
+        std::fs::create_dir_all(dir_path)?;
+        let _ = std::fs::remove_file(&path);
+        let listener = std::os::unix::net::UnixListener::bind(&path)?;
+
+        Ok(IPCServer {
+            listener,
+            url: url.to_string(),
+            subscribers: EventSubscribers::default(),
+        })
+    }
+
+    pub fn subscribers(&self) -> EventSubscribers {
+        self.subscribers.clone()
+    }
+
+    pub fn handle_connections(&self, libmpv_s: crossbeam::channel::Sender<LibMpvMessage>) {
+        for stream in self.listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    if let Err(err) = self.handle_connection(stream, &libmpv_s) {
+                        log::error!("IPCServer: {err:?}");
+                    }
+                }
+                Err(err) => log::error!("IPCServer: {err:?}"),
+            }
+        }
+    }
+
+    fn handle_connection(
+        &self,
+        stream: std::os::unix::net::UnixStream,
+        libmpv_s: &crossbeam::channel::Sender<LibMpvMessage>,
+    ) -> Result<(), SonicTunesError> {
+        use std::io::{BufRead, Write};
+
+        stream.set_read_timeout(Some(std::time::Duration::from_secs(1)))?;
+        let mut reader = std::io::BufReader::new(stream.try_clone()?);
+        let mut command_str = String::new();
+        reader.read_line(&mut command_str)?;
+        log::debug!("IPCServer: {command_str:?}");
+
+        if command_str.trim() == "attach" {
+            return self.attach(stream, reader, libmpv_s);
+        }
+
+        let response = handle_command(&command_str, libmpv_s)
+            .unwrap_or_else(|err| format!("error: {err:?}\n"));
+        (&stream).write_all(response.as_bytes())?;
+
+        Ok(())
+    }
+
+    fn attach(
+        &self,
+        mut stream: std::os::unix::net::UnixStream,
+        reader: std::io::BufReader<std::os::unix::net::UnixStream>,
+        libmpv_s: &crossbeam::channel::Sender<LibMpvMessage>,
+    ) -> Result<(), SonicTunesError> {
+        use std::io::{BufRead, Write};
+
+        stream.set_read_timeout(None)?;
+        stream.set_write_timeout(Some(std::time::Duration::from_secs(1)))?;
+        let attach_info = AttachInfo {
+            url: self.url.clone(),
+        };
+        writeln!(stream, "{}", serde_json::to_string(&attach_info)?)?;
+
+        // The mpv thread subscribes the client once the snapshot is sent, see LibMpvEventMessage::Subscribe.
+        let (state_s, state_r) = crossbeam::channel::unbounded();
+        let event_s = EventSubscribers::spawn_writer(stream, state_r);
+        libmpv_s.send(LibMpvMessage::Resync(state_s, event_s))?;
+
+        let libmpv_s = libmpv_s.clone();
+        std::thread::spawn(move || {
+            log::debug!("IPCServer: client attached");
+            for line in reader.lines().map_while(Result::ok) {
+                match serde_json::from_str::<LibMpvMessage>(&line) {
+                    Ok(msg) => {
+                        if libmpv_s.send(msg).is_err() {
+                            break;
+                        }
+                    }
+                    Err(err) => log::error!("IPCServer: {err:?}"),
+                }
+            }
+            log::debug!("IPCServer: client detached");
+        });
+
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+pub fn send_remote_command(command_str: &str) -> Result<String, SonicTunesError> {
+    use std::io::{Read, Write};
+
+    let path = get_socket_path().ok_or(SonicTunesError::IPCError(
+        "no runtime directory available".to_string(),
+    ))?;
+    let mut stream = std::os::unix::net::UnixStream::connect(&path)
+        .map_err(|err| SonicTunesError::IPCError(format!("{path}: {err}")))?;
+    stream.set_read_timeout(Some(std::time::Duration::from_secs(5)))?;
+    writeln!(stream, "{}", command_str.trim())?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+
+    Ok(response)
+}
+
+#[cfg(unix)]
+pub fn attach() -> Result<(AttachInfo, AttachedStream), SonicTunesError> {
+    use std::io::{BufRead, Write};
+
+    let path = get_socket_path().ok_or(SonicTunesError::IPCError(
+        "no runtime directory available".to_string(),
+    ))?;
+    let mut stream = std::os::unix::net::UnixStream::connect(&path)
+        .map_err(|err| SonicTunesError::IPCError(format!("{path}: {err}")))?;
+    writeln!(stream, "attach")?;
+    let mut reader = std::io::BufReader::new(stream);
+    let mut attach_info = String::new();
+    reader.read_line(&mut attach_info)?;
+
+    Ok((
+        serde_json::from_str(&attach_info)?,
+        AttachedStream { reader },
+    ))
+}
+
+#[cfg(unix)]
+pub struct AttachedStream {
+    reader: std::io::BufReader<std::os::unix::net::UnixStream>,
+}
+
+#[cfg(unix)]
+pub fn run_attached(
+    attached_stream: AttachedStream,
+    library_cache: LibraryCache,
+    tui_s: crossbeam::channel::Sender<LibMpvEventMessage>,
+    libmpv_r: crossbeam::channel::Receiver<LibMpvMessage>,
+) -> Result<(), SonicTunesError> {
+    use std::io::{BufRead, Write};
+
+    let mut stream = attached_stream.reader.get_ref().try_clone()?;
+    let reader = attached_stream.reader;
+    std::thread::spawn(move || {
+        for line in reader.lines().map_while(Result::ok) {
+            match serde_json::from_str::<LibMpvEventMessage>(&line) {
+                Ok(LibMpvEventMessage::LibraryRefreshed) => {
+                    // The instance refreshed its own cache, the attached TUI keeps a separate one.
+                    let library_cache = library_cache.clone();
+                    std::thread::spawn(move || {
+                        if let Err(err) = library_cache.refresh() {
+                            log::error!("LibraryCache: {err:?}");
+                        }
+                    });
+                }
+                Ok(msg) => {
+                    if tui_s.send(msg).is_err() {
+                        return;
+                    }
+                }
+                Err(err) => log::error!("Attach: {err:?}"),
+            }
+        }
+        log::debug!("Attach: connection closed");
+        let _ = tui_s.send(LibMpvEventMessage::Quit);
+    });
+
+    for msg in libmpv_r.iter() {
+        // Quitting the attached TUI only detaches it, the instance keeps playing.
+        if let LibMpvMessage::Quit = msg {
+            break;
+        }
+        writeln!(stream, "{}", serde_json::to_string(&msg)?)?;
+    }
+    let _ = stream.shutdown(std::net::Shutdown::Both);
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub struct AttachedStream;
+
+#[cfg(not(unix))]
+pub fn attach() -> Result<(AttachInfo, AttachedStream), SonicTunesError> {
+    Err(SonicTunesError::IPCError(
+        "not supported on this platform".to_string(),
+    ))
+}
+
+#[cfg(not(unix))]
+pub fn run_attached(
+    _attached_stream: AttachedStream,
+    _library_cache: LibraryCache,
+    _tui_s: crossbeam::channel::Sender<LibMpvEventMessage>,
+    _libmpv_r: crossbeam::channel::Receiver<LibMpvMessage>,
+) -> Result<(), SonicTunesError> {
+    Ok(())
+}
+
+#[cfg(not(unix))]
+#[derive(Clone, Default)]
+pub struct EventSubscribers;
+
+#[cfg(not(unix))]
+impl EventSubscribers {
+    pub fn subscribe(&self, _event_s: crossbeam::channel::Sender<String>) {}
+
+    pub fn broadcast(&self, _msg: &LibMpvEventMessage) {}
+}
+
+#[cfg(not(unix))]
+pub struct IPCServer;
+
+#[cfg(not(unix))]
+impl IPCServer {
+    pub fn bind(_url: &str) -> Result<Self, SonicTunesError> {
+        Err(SonicTunesError::IPCError(
+            "not supported on this platform".to_string(),
+        ))
+    }
+
+    pub fn subscribers(&self) -> EventSubscribers {
+        EventSubscribers
+    }
+
+    pub fn handle_connections(&self, _libmpv_s: crossbeam::channel::Sender<LibMpvMessage>) {}
+}
+
+#[cfg(not(unix))]
+pub fn send_remote_command(_command_str: &str) -> Result<String, SonicTunesError> {
+    Err(SonicTunesError::IPCError(
+        "not supported on this platform".to_string(),
+    ))
+}
+
+pub fn query_status() -> Result<PlayerStatus, SonicTunesError> {
+    let response = send_remote_command("status-json")?;
+
+    Ok(serde_json::from_str(&response)?)
+}
+
+pub fn remove_socket() {
+    if let Some(path) = get_socket_path() {
+        let _ = std::fs::remove_file(path);
+    }
+}
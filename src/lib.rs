@@ -1,17 +1,44 @@
-use rand::random_range;
-
 use crate::libmpv_handler::{LibMpvEventMessage, LibMpvMessage};
 
+pub mod audio_cache;
+pub mod config;
+pub mod history;
+pub mod ipc;
 pub mod libmpv_handler;
+pub mod library_cache;
 pub mod logger;
 pub mod mc_os_interface;
+pub mod network;
+pub mod shuffle;
 pub mod tui;
 
-#[derive(serde::Deserialize, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
 pub struct AudioFile {
     pub id: String,
     pub path: String,
     pub mime: String,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+}
+
+// Servers may report a generic mime type, so known audio extensions count too.
+const AUDIO_EXTENSIONS: [&str; 12] = [
+    "aac", "aiff", "alac", "ape", "flac", "m4a", "mp3", "ogg", "opus", "wav", "wma", "wv",
+];
+
+impl AudioFile {
+    pub fn is_playable(&self) -> bool {
+        self.mime.starts_with("audio/")
+            || std::path::Path::new(&self.path)
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .is_some_and(|extension| {
+                    AUDIO_EXTENSIONS
+                        .iter()
+                        .any(|audio| extension.eq_ignore_ascii_case(audio))
+                })
+    }
 }
 
 #[derive(serde::Deserialize)]
@@ -26,6 +53,14 @@ pub enum ProgramOption {
     PrintHelp,
     Volume(i64),
     Verbose,
+    NoCache,
+    RefreshInterval(u64),
+    ConfigPath(String),
+    Remote(String),
+    StatusFormat(String),
+    StatusJson,
+    Daemon,
+    NoTui,
 }
 
 #[derive(Debug)]
@@ -39,6 +74,34 @@ pub enum SonicTunesError {
     LibMpvMessageSendError(crossbeam::channel::SendError<LibMpvMessage>),
     LibMpvEventMessageSendError(crossbeam::channel::SendError<LibMpvEventMessage>),
     LibMpvError(libmpv2::Error),
+    SerdeJsonError(serde_json::Error),
+    ConfigError(String),
+    IPCError(String),
+    ServerStatus(String),
+    EmptyLibrary,
+}
+
+impl std::fmt::Display for SonicTunesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SonicTunesError::InvalidOption(option) => write!(f, "invalid option {option}"),
+            SonicTunesError::InvalidOptionsStructure => write!(f, "invalid input"),
+            SonicTunesError::ReqwestError(err) => write!(f, "request failed, {err}"),
+            SonicTunesError::SouvlakiError(err) => write!(f, "media controls, {err:?}"),
+            SonicTunesError::SystemTimeError(err) => write!(f, "system time, {err}"),
+            SonicTunesError::IOError(err) => write!(f, "{err}"),
+            SonicTunesError::LibMpvMessageSendError(_) => write!(f, "player is not running"),
+            SonicTunesError::LibMpvEventMessageSendError(_) => {
+                write!(f, "interface is not running")
+            }
+            SonicTunesError::LibMpvError(err) => write!(f, "mpv, {err:?}"),
+            SonicTunesError::SerdeJsonError(err) => write!(f, "{err}"),
+            SonicTunesError::ConfigError(err) => write!(f, "invalid config file {err}"),
+            SonicTunesError::IPCError(err) => write!(f, "{err}"),
+            SonicTunesError::ServerStatus(status) => write!(f, "invalid server status, {status}"),
+            SonicTunesError::EmptyLibrary => write!(f, "library is empty"),
+        }
+    }
 }
 
 impl From<reqwest::Error> for SonicTunesError {
@@ -83,35 +146,59 @@ impl From<libmpv2::Error> for SonicTunesError {
     }
 }
 
+impl From<serde_json::Error> for SonicTunesError {
+    fn from(err: serde_json::Error) -> Self {
+        SonicTunesError::SerdeJsonError(err)
+    }
+}
+
 pub fn process_args() -> Result<Vec<ProgramOption>, SonicTunesError> {
     let mut options = vec![];
     let mut args: Vec<String> = std::env::args().skip(1).collect();
 
-    let mut last_arg = args
-        .pop()
-        .or_else(|| load_url_from_config())
-        .ok_or(SonicTunesError::InvalidOptionsStructure)?;
-
-    if last_arg == "--help" {
-        options.push(ProgramOption::PrintHelp);
-        return Ok(options);
-    }
-
-    if last_arg.starts_with("--") {
-        args.push(last_arg);
-        last_arg = load_url_from_config().ok_or(SonicTunesError::InvalidOptionsStructure)?;
+    if let Some(i) = args.iter().position(|arg| arg == "--remote") {
+        if i + 1 >= args.len() {
+            return Err(SonicTunesError::InvalidOption(args.remove(i)));
+        }
+        options.push(ProgramOption::Remote(args.remove(i + 1)));
+        args.remove(i);
     }
 
-    let url = last_arg;
-    if !url.starts_with("http") {
-        return Err(SonicTunesError::InvalidOptionsStructure);
+    if let Some(last_arg) = args.last()
+        && !last_arg.starts_with("--")
+    {
+        let url = args.pop().unwrap();
+        if !url.starts_with("http") {
+            return Err(SonicTunesError::InvalidOptionsStructure);
+        }
+        options.push(ProgramOption::URL(url));
     }
-    options.push(ProgramOption::URL(url));
 
     for arg in args {
         let arg = match arg.as_str() {
             "--help" => Ok(ProgramOption::PrintHelp),
             "--verbose" => Ok(ProgramOption::Verbose),
+            "--no-cache" => Ok(ProgramOption::NoCache),
+            "--status-json" => Ok(ProgramOption::StatusJson),
+            "--daemon" => Ok(ProgramOption::Daemon),
+            "--no-tui" => Ok(ProgramOption::NoTui),
+            s if s.starts_with("--status-format=") => match s.split_once('=') {
+                Some((_, format)) => Ok(ProgramOption::StatusFormat(format.to_string())),
+                None => Err(SonicTunesError::InvalidOption(arg)),
+            },
+            s if s.starts_with("--config=") => match s.split_once('=') {
+                Some((_, path)) if !path.is_empty() => {
+                    Ok(ProgramOption::ConfigPath(path.to_string()))
+                }
+                _ => Err(SonicTunesError::InvalidOption(arg)),
+            },
+            s if s.starts_with("--refresh-interval=") => {
+                if let Some(Ok(min)) = s.split_once('=').map(|(_, s)| s.parse::<u64>()) {
+                    Ok(ProgramOption::RefreshInterval(min))
+                } else {
+                    Err(SonicTunesError::InvalidOption(arg))
+                }
+            }
             s if s.starts_with("--volume=") => {
                 if let Some(Ok(vol)) = s.split_once('=').map(|(_, s)| s.parse::<i8>()) {
                     if (0..=100).contains(&vol) {
@@ -132,85 +219,114 @@ pub fn process_args() -> Result<Vec<ProgramOption>, SonicTunesError> {
 }
 
 #[cfg(target_os = "linux")]
-fn load_url_from_config() -> Option<String> {
-    let config_file_path = std::env::var("XDG_CONFIG_HOME")
+pub fn get_config_dir_path() -> Option<String> {
+    std::env::var("XDG_CONFIG_HOME")
         .or(std::env::var("HOME").map(|s| format!("{s}/.config")))
-        .map(|path| format!("{path}/{}/config", env!("CARGO_PKG_NAME")));
-    if let Ok(path) = config_file_path {
-        if std::path::PathBuf::from(&path).is_file() {
-            return std::fs::read_to_string(path).ok();
-        }
-    }
+        .map(|path| format!("{path}/{}", env!("CARGO_PKG_NAME")))
+        .ok()
+}
+
+#[cfg(target_os = "windows")]
+pub fn get_config_dir_path() -> Option<String> {
+    std::env::var("APPDATA")
+        .map(|path| format!("{path}/{}", env!("CARGO_PKG_NAME")))
+        .ok()
+}
 
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+pub fn get_config_dir_path() -> Option<String> {
     None
 }
 
 #[cfg(target_os = "linux")]
-pub fn save_url_to_config(url: &str) {
-    let config_dir_path = std::env::var("XDG_CONFIG_HOME")
-        .or(std::env::var("HOME").map(|s| format!("{s}/.config")))
-        .map(|path| format!("{path}/{}", env!("CARGO_PKG_NAME")));
-    if let Ok(dir_path) = config_dir_path {
-        if !std::path::PathBuf::from(&dir_path).is_dir() {
-            std::fs::create_dir(dir_path.clone()).unwrap();
-        }
-        let config_file_path = format!("{dir_path}/config");
-        std::fs::write(config_file_path, url).unwrap();
-    }
+pub fn get_cache_dir_path() -> Option<String> {
+    std::env::var("XDG_CACHE_HOME")
+        .or(std::env::var("HOME").map(|s| format!("{s}/.cache")))
+        .map(|path| format!("{path}/{}", env!("CARGO_PKG_NAME")))
+        .ok()
 }
 
 #[cfg(target_os = "windows")]
-fn load_url_from_config() -> Option<String> {
-    let config_file_path =
-        std::env::var("APPDATA").map(|path| format!("{path}/{}/config", env!("CARGO_PKG_NAME")));
-    if let Ok(path) = config_file_path {
-        if std::path::PathBuf::from(&path).is_file() {
-            return std::fs::read_to_string(path).ok();
-        }
-    }
+pub fn get_cache_dir_path() -> Option<String> {
+    std::env::var("LOCALAPPDATA")
+        .map(|path| format!("{path}/{}/cache", env!("CARGO_PKG_NAME")))
+        .ok()
+}
 
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+pub fn get_cache_dir_path() -> Option<String> {
     None
 }
 
-#[cfg(target_os = "windows")]
-pub fn save_url_to_config(url: &str) {
-    let config_dir_path =
-        std::env::var("APPDATA").map(|path| format!("{path}/{}", env!("CARGO_PKG_NAME")));
-    if let Ok(dir_path) = config_dir_path {
-        if !std::path::PathBuf::from(&dir_path).is_dir() {
-            std::fs::create_dir(dir_path.clone()).unwrap();
-        }
-        let config_file_path = format!("{dir_path}/config");
-        std::fs::write(config_file_path, url).unwrap();
-    }
+#[cfg(target_os = "linux")]
+pub fn get_runtime_dir_path() -> Option<String> {
+    std::env::var("XDG_RUNTIME_DIR")
+        .map(|path| format!("{path}/{}", env!("CARGO_PKG_NAME")))
+        .ok()
+        .or_else(get_cache_dir_path)
 }
 
-#[cfg(not(any(target_os = "linux", target_os = "windows")))]
-fn load_url_from_config() -> Option<String> {
+#[cfg(not(target_os = "linux"))]
+pub fn get_runtime_dir_path() -> Option<String> {
     None
 }
 
-#[cfg(not(any(target_os = "linux", target_os = "windows")))]
-pub fn save_url_to_config(url: &str) {}
-
 pub fn print_help() {
     println!(
-        "Usage: {} [OPTIONS] SUBSONICVAULT_URL",
+        "Usage: {} [OPTIONS] [SUBSONICVAULT_URL]",
         env!("CARGO_PKG_NAME")
     );
     println!("       {} --help", env!("CARGO_PKG_NAME"));
+    println!("       {} --remote <COMMAND>", env!("CARGO_PKG_NAME"));
+    println!(
+        "       {} [--status-format=<format>] [--status-json]",
+        env!("CARGO_PKG_NAME")
+    );
     println!("Options:");
+    println!("\t --config=<path>");
     println!("\t --volume=<value>\t(0..100)");
+    println!("\t --refresh-interval=<minutes>");
+    println!("\t --no-cache");
+    println!("\t --daemon");
+    println!("\t --no-tui");
     println!("\t --verbose");
     println!("\t --help");
 }
 
+static REQWEST_TIMEOUT: std::sync::OnceLock<std::time::Duration> = std::sync::OnceLock::new();
+
+pub fn set_reqwest_timeout(timeout: std::time::Duration) {
+    let _ = REQWEST_TIMEOUT.set(timeout);
+}
+
 #[inline(always)]
 pub fn get_reqwest_client() -> reqwest::Result<reqwest::blocking::Client> {
     let user_agent: String = format!("{}/{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
 
     let reqwest_client = reqwest::blocking::Client::builder()
-        .timeout(std::time::Duration::from_secs(1))
+        .timeout(
+            REQWEST_TIMEOUT
+                .get()
+                .copied()
+                .unwrap_or(std::time::Duration::from_secs(1)),
+        )
+        .user_agent(user_agent)
+        .build()?;
+
+    Ok(reqwest_client)
+}
+
+pub fn get_download_client() -> reqwest::Result<reqwest::blocking::Client> {
+    let user_agent: String = format!("{}/{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+
+    let reqwest_client = reqwest::blocking::Client::builder()
+        .connect_timeout(
+            REQWEST_TIMEOUT
+                .get()
+                .copied()
+                .unwrap_or(std::time::Duration::from_secs(1)),
+        )
+        .timeout(None)
         .user_agent(user_agent)
         .build()?;
 
@@ -227,14 +343,22 @@ pub fn reqwest_get(url: &str) -> reqwest::Result<reqwest::blocking::Response> {
     Ok(response)
 }
 
-pub fn get_random_audiofile(url: &str) -> Result<AudioFile, SonicTunesError> {
-    let mut url_files = url.trim_end_matches('/').to_string();
-    url_files.push_str("/files");
-    let files_response = reqwest_get(&url_files)?;
-    let audiofiles = files_response.json::<Vec<AudioFile>>()?;
-    let id = random_range(0..audiofiles.len());
+pub fn ping_server(url: &str) -> Result<(), SonicTunesError> {
+    let response = reqwest_get(&format!("{}/ping", url.trim_end_matches('/')))?;
+    let ping_response: PingResponse = response.json()?;
+    if ping_response.status != "ok" {
+        return Err(SonicTunesError::ServerStatus(ping_response.status));
+    }
+
+    Ok(())
+}
+
+pub fn secs_to_hms(seconds: u64) -> String {
+    let h = seconds / 3600;
+    let m = (seconds - h * 3600) / 60;
+    let s = seconds - h * 3600 - m * 60;
 
-    Ok(audiofiles[id].clone())
+    format!("{h:02}:{m:02}:{s:02}")
 }
 
 pub fn audiofile_to_url(url: &str, audiofile: &AudioFile) -> String {
@@ -1,6 +1,15 @@
-use crate::{SonicTunesError, audiofile_to_url, get_random_audiofile};
+use crate::{
+    AudioFile, SonicTunesError,
+    audio_cache::AudioCache,
+    audiofile_to_url,
+    history::{HistoryEntry, append_to_history},
+    library_cache::LibraryCache,
+    network::{NetworkResponse, NetworkWorker, PingReason},
+    secs_to_hms,
+    shuffle::{ShuffleBag, ShuffleMode},
+};
 
-#[derive(Debug)]
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
 pub enum LibMpvMessage {
     Quit,
     UpdateVolume(i64),
@@ -13,48 +22,913 @@ pub enum LibMpvMessage {
     PlayNext,
     PlayPrevious,
     Stop,
+    Play(Vec<AudioFile>),
+    Enqueue(Vec<AudioFile>),
+    EnqueueNext(Vec<AudioFile>),
+    Dequeue(usize),
+    QueueMoveUp(usize),
+    QueueMoveDown(usize),
+    ClearQueue,
+    SetShuffleMode(ShuffleMode),
+    SetRepeatMode(RepeatMode),
+    CycleRepeatMode,
+    PlayIndex(usize),
+    RemoveIndex(usize),
+    #[serde(skip)]
+    Status(crossbeam::channel::Sender<PlayerStatus>),
+    #[serde(skip)]
+    Resync(
+        crossbeam::channel::Sender<LibMpvEventMessage>,
+        crossbeam::channel::Sender<String>,
+    ),
+    PauseAfter(u64),
+    QuitAfter(u64),
+    Download(Vec<AudioFile>),
+    RefreshLibrary,
 }
 
-#[derive(Debug)]
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
 pub enum LibMpvEventMessage {
-    StartFile,
-    PlaybackRestart(bool),
-    PlaybackPause,
-    PlaybackResume,
-    FileLoaded(FileLoadedData),
-    VolumeUpdate(i64),
-    PositionUpdate(f64),
-    DurationUpdate(f64),
+    StateUpdate(PlayerState),
+    FileLoaded(Box<FileLoadedData>),
+    CoverArtUpdate(Option<String>),
+    LyricsUpdate(Option<String>),
     PlaylistPosUpdate(i64),
+    QueueUpdate(Vec<AudioFile>),
+    ShuffleModeUpdate(ShuffleMode),
+    RepeatModeUpdate(RepeatMode),
+    TimerUpdate(Option<PlaybackTimer>),
+    ConnectionUpdate(Option<std::time::SystemTime>, bool),
+    LibraryRefreshed,
+    Error(String),
     Quit,
+    // Registers an IPC subscriber in the event forwarder, right after its snapshot.
+    #[serde(skip)]
+    Subscribe(crossbeam::channel::Sender<String>),
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RepeatMode {
+    Off,
+    One,
+    All,
+    Stop,
+}
+
+impl std::str::FromStr for RepeatMode {
+    type Err = ();
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        match str {
+            "off" => Ok(RepeatMode::Off),
+            "one" => Ok(RepeatMode::One),
+            "all" => Ok(RepeatMode::All),
+            "stop" => Ok(RepeatMode::Stop),
+            _ => Err(()),
+        }
+    }
+}
+
+impl RepeatMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RepeatMode::Off => "off",
+            RepeatMode::One => "one",
+            RepeatMode::All => "all",
+            RepeatMode::Stop => "stop",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            RepeatMode::Off => RepeatMode::All,
+            RepeatMode::All => RepeatMode::One,
+            RepeatMode::One => RepeatMode::Stop,
+            RepeatMode::Stop => RepeatMode::Off,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum ReconnectAction {
+    LoadNext,
+    Reload(usize, f64),
+    Online,
 }
 
 #[derive(Debug)]
-pub struct FileLoadedData {
-    pub media_title: String,
+struct Reconnect {
+    action: ReconnectAction,
+    attempt: u32,
+    retry_at: std::time::SystemTime,
+    pinging: bool,
+}
+
+const MAX_RECONNECT_DELAY_SECS: u64 = 60;
+
+#[derive(Debug)]
+struct Prefetched {
+    index: usize,
+    from_queue: bool,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum TimerAction {
+    Pause,
+    Quit,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy)]
+pub struct PlaybackTimer {
+    pub action: TimerAction,
+    pub deadline: std::time::SystemTime,
+}
+
+impl PlaybackTimer {
+    pub fn remaining(&self) -> std::time::Duration {
+        self.deadline
+            .duration_since(std::time::SystemTime::now())
+            .unwrap_or_default()
+    }
+
+    pub fn generate_str(&self) -> String {
+        let prefix = match self.action {
+            TimerAction::Pause => "P",
+            TimerAction::Quit => "Q",
+        };
+
+        format!("{prefix}: {}", secs_to_hms(self.remaining().as_secs()))
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy)]
+pub struct PlayerState {
+    pub position: f64,
+    pub duration: f64,
+    pub paused: bool,
+    pub ready: bool,
+    pub volume: i64,
+    pub updated_at: std::time::SystemTime,
+}
+
+impl PlayerState {
+    fn new(volume: i64) -> Self {
+        PlayerState {
+            position: 0.0,
+            duration: 0.0,
+            paused: true,
+            ready: false,
+            volume,
+            updated_at: std::time::SystemTime::now(),
+        }
+    }
+
+    // Extrapolates from the last time-pos reported by mpv so consumers can redraw between updates.
+    pub fn position(&self) -> f64 {
+        if !self.ready {
+            return 0.0;
+        }
+        if self.paused {
+            return self.position;
+        }
+        let elapsed = self.updated_at.elapsed().unwrap_or_default().as_secs_f64();
+        let position = self.position + elapsed;
+        if self.duration > 0.0 {
+            position.min(self.duration)
+        } else {
+            position
+        }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.ready && !self.paused
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct PlayerStatus {
+    pub title: Option<String>,
     pub artist: Option<String>,
     pub album: Option<String>,
+    pub position: f64,
     pub duration: f64,
+    pub paused: bool,
+    pub stopped: bool,
     pub volume: i64,
+    pub shuffle_mode: ShuffleMode,
+    pub repeat_mode: RepeatMode,
+    pub pause_after: Option<u64>,
+    pub quit_after: Option<u64>,
+}
+
+impl PlayerStatus {
+    pub fn generate_str(&self) -> String {
+        let mut status_str = format!("status {}\n", self.status_str());
+        if let Some(ref title) = self.title {
+            status_str.push_str(&format!("title {title}\n"));
+        }
+        if let Some(ref artist) = self.artist {
+            status_str.push_str(&format!("artist {artist}\n"));
+        }
+        if let Some(ref album) = self.album {
+            status_str.push_str(&format!("album {album}\n"));
+        }
+        status_str.push_str(&format!("position {:.0}\n", self.position));
+        status_str.push_str(&format!("duration {:.0}\n", self.duration));
+        status_str.push_str(&format!("volume {}\n", self.volume));
+        status_str.push_str(&format!("shuffle {}\n", self.shuffle_mode.as_str()));
+        status_str.push_str(&format!("repeat {}\n", self.repeat_mode.as_str()));
+        if let Some(pause_after) = self.pause_after {
+            status_str.push_str(&format!("pause_after {pause_after}\n"));
+        }
+        if let Some(quit_after) = self.quit_after {
+            status_str.push_str(&format!("quit_after {quit_after}\n"));
+        }
+
+        status_str
+    }
+
+    pub fn format(&self, format: &str) -> String {
+        let timer = self
+            .pause_after
+            .map(|secs| format!("P: {}", secs_to_hms(secs)))
+            .or(self
+                .quit_after
+                .map(|secs| format!("Q: {}", secs_to_hms(secs))))
+            .unwrap_or_default();
+
+        format
+            .replace("{status}", self.status_str())
+            .replace("{title}", self.title.as_deref().unwrap_or_default())
+            .replace("{artist}", self.artist.as_deref().unwrap_or_default())
+            .replace("{album}", self.album.as_deref().unwrap_or_default())
+            .replace("{position}", &secs_to_hms(self.position as u64))
+            .replace("{duration}", &secs_to_hms(self.duration as u64))
+            .replace("{volume}", &self.volume.to_string())
+            .replace("{shuffle}", self.shuffle_mode.as_str())
+            .replace("{repeat}", self.repeat_mode.as_str())
+            .replace("{timer}", &timer)
+    }
+
+    pub fn generate_waybar_json(&self, format: &str) -> Result<String, serde_json::Error> {
+        let percentage = if self.duration > 0.0 {
+            (self.position / self.duration * 100.0) as u64
+        } else {
+            0
+        };
+        let tooltip = [&self.title, &self.artist, &self.album]
+            .into_iter()
+            .flatten()
+            .cloned()
+            .collect::<Vec<String>>()
+            .join("\n");
+
+        serde_json::to_string(&serde_json::json!({
+            "text": self.format(format),
+            "tooltip": tooltip,
+            "alt": self.status_str(),
+            "class": self.status_str(),
+            "percentage": percentage,
+        }))
+    }
+
+    fn status_str(&self) -> &'static str {
+        if self.stopped {
+            "stopped"
+        } else if self.paused {
+            "paused"
+        } else {
+            "playing"
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct FileLoadedData {
+    pub media_title: String,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub audiofile: Option<AudioFile>,
 }
 
 pub struct LibMpvHandler {
     mpv: libmpv2::Mpv,
+    queue: std::collections::VecDeque<AudioFile>,
+    shuffle_mode: ShuffleMode,
+    shuffle_bag: ShuffleBag,
+    repeat_mode: RepeatMode,
+    playlist: Vec<AudioFile>,
+    history_entry: Option<HistoryEntry>,
+    state: PlayerState,
+    timer: Option<PlaybackTimer>,
+    reconnect: Option<Reconnect>,
+    playing_index: Option<usize>,
+    resume_position: Option<f64>,
+    ignore_playnext_until_load: bool,
+    audio_cache: AudioCache,
+    offline: bool,
+    prefetched: Option<Prefetched>,
+    network: NetworkWorker,
+    refreshing_library: bool,
+    cover_art: Option<String>,
+    cover_art_pending: bool,
+    cover_art_count: u64,
+    lyrics: Option<String>,
+    // Id of the sidecar lyrics file being fetched.
+    lyrics_pending: Option<String>,
 }
 
 impl LibMpvHandler {
-    pub fn initialize_libmpv(volume: i64) -> Result<Self, libmpv2::Error> {
+    pub fn initialize_libmpv(
+        volume: i64,
+        shuffle_mode: ShuffleMode,
+        repeat_mode: RepeatMode,
+        audio_cache: AudioCache,
+        shuffle_bag: ShuffleBag,
+        network: NetworkWorker,
+    ) -> Result<Self, libmpv2::Error> {
         let mpv = libmpv2::Mpv::new()?;
         mpv.set_property("volume", volume)?;
         mpv.set_property("vo", "null")?;
+        mpv.set_property("gapless-audio", "yes")?;
+        mpv.set_property("prefetch-playlist", "yes")?;
+        // Cover art is read back from screenshots, see CoverArt::load. They are
+        // deleted right after, so compressing them would only cost time.
+        mpv.set_property("screenshot-format", "png")?;
+        mpv.set_property("screenshot-png-compression", 0)?;
+        if let Err(err) = mpv.set_property("vf", "scale=512:-2") {
+            log::error!("vf: {err:?}");
+        }
 
         mpv.disable_deprecated_events()?;
 
-        Ok(LibMpvHandler { mpv })
+        let mut mpv_handler = LibMpvHandler {
+            mpv,
+            queue: std::collections::VecDeque::new(),
+            shuffle_mode,
+            shuffle_bag,
+            repeat_mode: RepeatMode::Off,
+            playlist: Vec::new(),
+            history_entry: None,
+            state: PlayerState::new(volume),
+            timer: None,
+            reconnect: None,
+            playing_index: None,
+            resume_position: None,
+            ignore_playnext_until_load: true,
+            audio_cache,
+            offline: false,
+            prefetched: None,
+            network,
+            refreshing_library: false,
+            cover_art: None,
+            cover_art_pending: false,
+            cover_art_count: 0,
+            lyrics: None,
+            lyrics_pending: None,
+        };
+        mpv_handler.set_repeat_mode(repeat_mode)?;
+
+        Ok(mpv_handler)
+    }
+
+    pub fn load_file(&mut self, url: &str, audiofile: AudioFile) -> Result<(), libmpv2::Error> {
+        self.mpv.command(
+            "loadfile",
+            &[&self.audiofile_location(url, &audiofile), "append-play"],
+        )?;
+        self.playlist.push(audiofile);
+
+        Ok(())
+    }
+
+    pub fn append_file(&mut self, url: &str, audiofile: AudioFile) -> Result<(), libmpv2::Error> {
+        self.mpv.command(
+            "loadfile",
+            &[&self.audiofile_location(url, &audiofile), "append"],
+        )?;
+        self.playlist.push(audiofile);
+
+        Ok(())
+    }
+
+    fn audiofile_location(&self, url: &str, audiofile: &AudioFile) -> String {
+        self.audio_cache
+            .path(audiofile)
+            .unwrap_or_else(|| audiofile_to_url(url, audiofile))
+    }
+
+    fn finish_history_entry(&mut self, skipped: bool) {
+        if let Some(mut history_entry) = self.history_entry.take() {
+            history_entry.listened = self.state.position;
+            history_entry.skipped = skipped;
+            if let Err(err) = append_to_history(&history_entry) {
+                log::error!("History: {err:?}");
+            }
+        }
+    }
+
+    fn next_audiofile(
+        &mut self,
+        library_cache: &LibraryCache,
+    ) -> Result<Option<AudioFile>, SonicTunesError> {
+        if self.offline {
+            return Ok(self.next_offline_audiofile());
+        }
+        if let Some(audiofile) = self.queue.pop_front() {
+            return Ok(Some(audiofile));
+        }
+
+        match self.shuffle_mode {
+            ShuffleMode::Random => {
+                self.refresh_library(library_cache);
+                Ok(Some(library_cache.random_audiofile()?))
+            }
+            ShuffleMode::Bag => {
+                self.refresh_library(library_cache);
+                let audiofile = self.shuffle_bag.next(library_cache);
+                Ok(Some(audiofile.ok_or(SonicTunesError::EmptyLibrary)?))
+            }
+            ShuffleMode::Off => Ok(None),
+        }
+    }
+
+    fn refresh_library(&mut self, library_cache: &LibraryCache) {
+        if !self.refreshing_library && library_cache.is_stale() {
+            self.refreshing_library = true;
+            self.network.refresh_library(false);
+        }
+    }
+
+    fn next_offline_audiofile(&mut self) -> Option<AudioFile> {
+        let cached = self
+            .queue
+            .iter()
+            .position(|audiofile| self.audio_cache.contains(&audiofile.id));
+        match cached {
+            Some(i) => self.queue.remove(i),
+            None if self.shuffle_mode == ShuffleMode::Off => None,
+            None => self.audio_cache.random_audiofile(),
+        }
+    }
+
+    pub fn load_next_file(
+        &mut self,
+        url: &str,
+        library_cache: &LibraryCache,
+    ) -> Result<bool, SonicTunesError> {
+        match self.next_audiofile(library_cache)? {
+            Some(audiofile) => {
+                log::debug!("Playing: {}", audiofile.path);
+                self.load_file(url, audiofile)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn load_next_file_or_reconnect(
+        &mut self,
+        url: &str,
+        library_cache: &LibraryCache,
+        tui_s: &crossbeam::channel::Sender<LibMpvEventMessage>,
+    ) -> Result<bool, SonicTunesError> {
+        match self.load_next_file(url, library_cache) {
+            Ok(loaded) => Ok(loaded),
+            Err(err) => {
+                log::error!("LoadNextFile: {err:?}");
+                let loaded = self.recover_load_next(url, library_cache)?;
+                self.send_connection_update(tui_s)?;
+                Ok(loaded)
+            }
+        }
+    }
+
+    fn prefetch_next(
+        &mut self,
+        url: &str,
+        library_cache: &LibraryCache,
+        tui_s: &crossbeam::channel::Sender<LibMpvEventMessage>,
+    ) -> Result<(), SonicTunesError> {
+        let reconnecting = self
+            .reconnect
+            .as_ref()
+            .is_some_and(|reconnect| !matches!(reconnect.action, ReconnectAction::Online));
+        if self.repeat_mode != RepeatMode::Off || self.prefetched.is_some() || reconnecting {
+            return Ok(());
+        }
+        let pos = self.mpv.get_property::<i64>("playlist-playing-pos")?;
+        let count = self.mpv.get_property::<i64>("playlist-count")?;
+        if pos == -1 || pos != count - 1 {
+            return Ok(());
+        }
+
+        let queue_len = self.queue.len();
+        match self.next_audiofile(library_cache) {
+            Ok(Some(audiofile)) => {
+                log::debug!("Prefetch: {}", audiofile.path);
+                self.append_file(url, audiofile)?;
+                self.prefetched = Some(Prefetched {
+                    index: count as usize,
+                    from_queue: self.queue.len() < queue_len,
+                });
+                self.send_queue_update(tui_s)?;
+            }
+            Ok(None) => (),
+            // Loading is retried once the current file ends.
+            Err(err) => log::error!("Prefetch: {err:?}"),
+        }
+
+        Ok(())
+    }
+
+    fn cancel_prefetch(&mut self) -> Result<(), SonicTunesError> {
+        let Some(prefetched) = self.prefetched.take() else {
+            return Ok(());
+        };
+        let pos = self.mpv.get_property::<i64>("playlist-playing-pos")?;
+        if usize::try_from(pos).ok() == Some(prefetched.index)
+            || prefetched.index >= self.playlist.len()
+        {
+            return Ok(());
+        }
+        log::debug!("Prefetch: cancel");
+        self.mpv
+            .command("playlist-remove", &[&prefetched.index.to_string()])?;
+        let audiofile = self.playlist.remove(prefetched.index);
+        if prefetched.from_queue {
+            self.queue.push_front(audiofile);
+        } else if self.shuffle_mode == ShuffleMode::Bag && !self.offline {
+            self.shuffle_bag.put_back(&audiofile);
+        }
+
+        Ok(())
+    }
+
+    fn cancel_prefetch_from(&mut self, from_queue: bool) -> Result<(), SonicTunesError> {
+        if self
+            .prefetched
+            .as_ref()
+            .is_some_and(|prefetched| prefetched.from_queue == from_queue)
+        {
+            self.cancel_prefetch()?;
+        }
+
+        Ok(())
+    }
+
+    pub fn recover_load_next(
+        &mut self,
+        url: &str,
+        library_cache: &LibraryCache,
+    ) -> Result<bool, SonicTunesError> {
+        if self.enter_offline_mode() {
+            return self.load_next_file(url, library_cache);
+        }
+        self.schedule_reconnect(ReconnectAction::LoadNext, 0);
+
+        Ok(false)
+    }
+
+    pub fn enter_offline_mode(&mut self) -> bool {
+        if self.offline {
+            return true;
+        }
+        if self.audio_cache.is_empty() {
+            return false;
+        }
+        log::debug!("Offline: playing cached files");
+        self.offline = true;
+        self.schedule_reconnect(ReconnectAction::Online, 0);
+
+        true
+    }
+
+    fn schedule_reconnect(&mut self, action: ReconnectAction, attempt: u32) {
+        let delay = 2u64.saturating_pow(attempt).min(MAX_RECONNECT_DELAY_SECS);
+        log::debug!("Reconnect: {action:?} in {delay}s");
+        self.reconnect = Some(Reconnect {
+            action,
+            attempt,
+            retry_at: std::time::SystemTime::now() + std::time::Duration::from_secs(delay),
+            pinging: false,
+        });
+    }
+
+    // Returns whether a cover art track is selected, embedded in the file or a sidecar image.
+    fn load_cover_art(
+        &self,
+        url: &str,
+        library_cache: &LibraryCache,
+        audiofile: Option<&AudioFile>,
+    ) -> bool {
+        if self
+            .mpv
+            .get_property::<i64>("current-tracks/video/id")
+            .is_ok()
+        {
+            return true;
+        }
+        if self.offline {
+            return false;
+        }
+        let Some(cover) =
+            audiofile.and_then(|audiofile| find_sidecar_cover(&library_cache.files(), audiofile))
+        else {
+            return false;
+        };
+
+        let cover_url = audiofile_to_url(url, &cover);
+        let result = self
+            .mpv
+            .command("video-add", &[&cover_url, "select", "cover", "", "yes"])
+            // mpv before 0.35 has no albumart argument.
+            .or_else(|_| self.mpv.command("video-add", &[&cover_url, "select"]));
+        if let Err(err) = result {
+            log::error!("CoverArt: {err:?}");
+            return false;
+        }
+
+        true
+    }
+
+    fn capture_cover_art(
+        &mut self,
+        tui_s: &crossbeam::channel::Sender<LibMpvEventMessage>,
+        mc_os_s: &crossbeam::channel::Sender<LibMpvEventMessage>,
+    ) -> Result<(), SonicTunesError> {
+        if !self.cover_art_pending {
+            return Ok(());
+        }
+        let dir_path = std::env::temp_dir().join(env!("CARGO_PKG_NAME"));
+        std::fs::create_dir_all(&dir_path)?;
+        self.cover_art_count += 1;
+        let path = dir_path
+            .join(format!(
+                "cover-{}-{}.png",
+                std::process::id(),
+                self.cover_art_count
+            ))
+            .to_string_lossy()
+            .to_string();
+        // Fails until the first frame is decoded, retried on the next reconfig or restart.
+        if let Err(err) = self.mpv.command("screenshot-to-file", &[&path, "video"]) {
+            log::debug!("CoverArt: {err:?}");
+            return Ok(());
+        }
+
+        self.cover_art_pending = false;
+        self.remove_cover_art();
+        self.cover_art = Some(path);
+        tui_s.send(LibMpvEventMessage::CoverArtUpdate(self.cover_art.clone()))?;
+        mc_os_s.send(LibMpvEventMessage::CoverArtUpdate(self.cover_art.clone()))?;
+
+        Ok(())
+    }
+
+    fn remove_cover_art(&mut self) {
+        if let Some(path) = self.cover_art.take()
+            && let Err(err) = std::fs::remove_file(&path)
+        {
+            log::error!("CoverArt: {path}: {err:?}");
+        }
+    }
+
+    // Embedded lyrics are shown right away, a sidecar .lrc replaces them once fetched.
+    fn load_lyrics(&mut self, library_cache: &LibraryCache, audiofile: Option<&AudioFile>) {
+        self.lyrics = ["metadata/by-key/lyrics", "metadata/by-key/unsyncedlyrics"]
+            .iter()
+            .find_map(|key| self.mpv.get_property::<String>(key).ok())
+            .filter(|lyrics| !lyrics.trim().is_empty());
+        self.lyrics_pending = None;
+        if self.offline {
+            return;
+        }
+        if let Some(lrc) =
+            audiofile.and_then(|audiofile| find_sidecar_lyrics(&library_cache.files(), audiofile))
+        {
+            self.lyrics_pending = Some(lrc.id.clone());
+            self.network.fetch_lyrics(lrc);
+        }
+    }
+
+    fn send_connection_update(
+        &self,
+        tui_s: &crossbeam::channel::Sender<LibMpvEventMessage>,
+    ) -> Result<(), SonicTunesError> {
+        tui_s.send(LibMpvEventMessage::ConnectionUpdate(
+            self.reconnect.as_ref().map(|reconnect| reconnect.retry_at),
+            self.offline,
+        ))?;
+
+        Ok(())
+    }
+
+    fn retry_reconnect(&mut self) {
+        if let Some(ref mut reconnect) = self.reconnect
+            && !reconnect.pinging
+            && reconnect.retry_at <= std::time::SystemTime::now()
+        {
+            reconnect.pinging = true;
+            self.network.ping(PingReason::Reconnect);
+        }
+    }
+
+    fn finish_reconnect(
+        &mut self,
+        ping_result: Result<(), String>,
+        url: &str,
+        library_cache: &LibraryCache,
+        tui_s: &crossbeam::channel::Sender<LibMpvEventMessage>,
+    ) -> Result<(), SonicTunesError> {
+        let Some(reconnect) = self.reconnect.take() else {
+            return Ok(());
+        };
+        let result = ping_result.and_then(|_| match reconnect.action {
+            ReconnectAction::LoadNext => self
+                .load_next_file(url, library_cache)
+                .map(|_| ())
+                .map_err(|err| err.to_string()),
+            ReconnectAction::Reload(index, position) => {
+                self.resume_position = Some(position).filter(|&position| position > 0.0);
+                self.mpv
+                    .command("playlist-play-index", &[&index.to_string()])
+                    .map_err(|err| err.to_string())
+            }
+            ReconnectAction::Online => {
+                log::debug!("Online: streaming from {url}");
+                self.offline = false;
+                Ok(())
+            }
+        });
+        if let Err(err) = result {
+            log::error!("Reconnect: {err}");
+            self.schedule_reconnect(reconnect.action, reconnect.attempt + 1);
+        } else {
+            self.send_queue_update(tui_s)?;
+            self.prefetch_next(url, library_cache, tui_s)?;
+        }
+        self.send_connection_update(tui_s)?;
+
+        Ok(())
+    }
+
+    fn send_queue_update(
+        &self,
+        tui_s: &crossbeam::channel::Sender<LibMpvEventMessage>,
+    ) -> Result<(), SonicTunesError> {
+        self.mpv
+            .set_property("loop-playlist", self.loop_playlist())?;
+        // A track prefetched from the queue is still shown as its head.
+        let prefetched = self
+            .prefetched
+            .as_ref()
+            .filter(|prefetched| prefetched.from_queue)
+            .and_then(|prefetched| self.playlist.get(prefetched.index));
+        tui_s.send(LibMpvEventMessage::QueueUpdate(
+            prefetched
+                .into_iter()
+                .chain(self.queue.iter())
+                .cloned()
+                .collect(),
+        ))?;
+
+        Ok(())
+    }
+
+    // Maps an index into the queue sent to clients to one into `self.queue`. Edits involving the
+    // head cancel a prefetch from the queue, so that track goes back into `self.queue`.
+    fn queue_index(
+        &mut self,
+        i: usize,
+        touches_head: bool,
+    ) -> Result<Option<usize>, SonicTunesError> {
+        if !self
+            .prefetched
+            .as_ref()
+            .is_some_and(|prefetched| prefetched.from_queue)
+        {
+            return Ok(Some(i));
+        }
+        if touches_head {
+            self.cancel_prefetch()?;
+            return Ok(Some(i));
+        }
+
+        Ok(i.checked_sub(1))
+    }
+
+    // Looping is held off while tracks are queued, so they play before mpv wraps around.
+    fn loop_playlist(&self) -> &'static str {
+        if self.repeat_mode == RepeatMode::All && self.queue.is_empty() {
+            "inf"
+        } else {
+            "no"
+        }
+    }
+
+    fn set_repeat_mode(&mut self, repeat_mode: RepeatMode) -> Result<(), libmpv2::Error> {
+        let loop_file = if repeat_mode == RepeatMode::One {
+            "inf"
+        } else {
+            "no"
+        };
+        let keep_open = if repeat_mode == RepeatMode::Stop {
+            "always"
+        } else {
+            "no"
+        };
+        self.mpv.set_property("loop-file", loop_file)?;
+        self.mpv.set_property("keep-open", keep_open)?;
+        self.repeat_mode = repeat_mode;
+        self.mpv
+            .set_property("loop-playlist", self.loop_playlist())?;
+
+        Ok(())
+    }
+
+    fn broadcast_state(
+        &mut self,
+        tui_s: &crossbeam::channel::Sender<LibMpvEventMessage>,
+        mc_os_s: &crossbeam::channel::Sender<LibMpvEventMessage>,
+    ) -> Result<(), SonicTunesError> {
+        self.state.updated_at = std::time::SystemTime::now();
+        tui_s.send(LibMpvEventMessage::StateUpdate(self.state))?;
+        mc_os_s.send(LibMpvEventMessage::StateUpdate(self.state))?;
+
+        Ok(())
+    }
+
+    fn send_state(
+        &self,
+        state_s: &crossbeam::channel::Sender<LibMpvEventMessage>,
+    ) -> Result<(), SonicTunesError> {
+        let loaded = self
+            .prefetched
+            .as_ref()
+            .map_or(self.playlist.len(), |prefetched| prefetched.index);
+        for (i, audiofile) in self.playlist.iter().take(loaded).enumerate() {
+            state_s.send(LibMpvEventMessage::PlaylistPosUpdate(i as i64))?;
+            state_s.send(LibMpvEventMessage::FileLoaded(Box::new(FileLoadedData {
+                media_title: audiofile
+                    .title
+                    .clone()
+                    .unwrap_or_else(|| audiofile.path.clone()),
+                artist: audiofile.artist.clone(),
+                album: audiofile.album.clone(),
+                audiofile: Some(audiofile.clone()),
+            })))?;
+        }
+
+        let pos = self.mpv.get_property::<i64>("playlist-playing-pos")?;
+        if pos != -1 {
+            state_s.send(LibMpvEventMessage::PlaylistPosUpdate(pos))?;
+            if let Some(ref history_entry) = self.history_entry {
+                state_s.send(LibMpvEventMessage::FileLoaded(Box::new(FileLoadedData {
+                    media_title: history_entry.title.clone().unwrap_or_default(),
+                    artist: history_entry.artist.clone(),
+                    album: history_entry.album.clone(),
+                    audiofile: Some(history_entry.to_audiofile()),
+                })))?;
+            }
+        }
+        state_s.send(LibMpvEventMessage::StateUpdate(self.state))?;
+        state_s.send(LibMpvEventMessage::CoverArtUpdate(self.cover_art.clone()))?;
+        state_s.send(LibMpvEventMessage::LyricsUpdate(self.lyrics.clone()))?;
+        self.send_queue_update(state_s)?;
+        state_s.send(LibMpvEventMessage::ShuffleModeUpdate(self.shuffle_mode))?;
+        state_s.send(LibMpvEventMessage::RepeatModeUpdate(self.repeat_mode))?;
+        state_s.send(LibMpvEventMessage::TimerUpdate(self.timer))?;
+        self.send_connection_update(state_s)?;
+
+        Ok(())
     }
 
-    pub fn load_file(&self, file: &str) -> Result<(), libmpv2::Error> {
-        self.mpv.command("loadfile", &[file, "append-play"])
+    fn player_status(&self) -> Result<PlayerStatus, libmpv2::Error> {
+        let stopped = self.mpv.get_property::<bool>("idle-active")?;
+        let history_entry = self.history_entry.as_ref().filter(|_| !stopped);
+
+        Ok(PlayerStatus {
+            title: history_entry.and_then(|entry| entry.title.clone()),
+            artist: history_entry.and_then(|entry| entry.artist.clone()),
+            album: history_entry.and_then(|entry| entry.album.clone()),
+            position: if stopped { 0.0 } else { self.state.position },
+            duration: if stopped { 0.0 } else { self.state.duration },
+            paused: self.state.paused,
+            stopped,
+            volume: self.state.volume,
+            shuffle_mode: self.shuffle_mode,
+            repeat_mode: self.repeat_mode,
+            pause_after: self
+                .timer
+                .filter(|timer| timer.action == TimerAction::Pause)
+                .map(|timer| timer.remaining().as_secs()),
+            quit_after: self
+                .timer
+                .filter(|timer| timer.action == TimerAction::Quit)
+                .map(|timer| timer.remaining().as_secs()),
+        })
     }
 
     pub fn create_client(&self) -> Result<libmpv2::Mpv, libmpv2::Error> {
@@ -65,6 +939,7 @@ impl LibMpvHandler {
         client.observe_property("volume", libmpv2::Format::Int64, 0)?;
         client.observe_property("duration/full", libmpv2::Format::Double, 0)?;
         client.observe_property("playlist-playing-pos", libmpv2::Format::Int64, 0)?;
+        client.observe_property("time-pos", libmpv2::Format::Double, 0)?;
 
         Ok(client)
     }
@@ -73,196 +948,649 @@ impl LibMpvHandler {
         &mut self,
         mut mpv_client: libmpv2::Mpv,
         url: &str,
+        library_cache: LibraryCache,
         tui_s: crossbeam::channel::Sender<LibMpvEventMessage>,
         mc_os_s: crossbeam::channel::Sender<LibMpvEventMessage>,
         libmpv_r: crossbeam::channel::Receiver<LibMpvMessage>,
     ) -> Result<(), SonicTunesError> {
-        let mut ignore_playnext_until_load = true;
-        loop {
-            let ev = mpv_client
-                .wait_event(0.016)
-                .unwrap_or(Err(libmpv2::Error::Null));
-
-            if ev.is_ok() {
-                log::debug!("Event {ev:?}");
-            }
-            match ev {
-                Ok(event) => match event {
-                    libmpv2::events::Event::StartFile => {
-                        tui_s.send(LibMpvEventMessage::StartFile)?;
-                        mc_os_s.send(LibMpvEventMessage::StartFile)?;
-                    }
-                    libmpv2::events::Event::PlaybackRestart => {
-                        let pause = self.mpv.get_property::<bool>("pause")?;
-                        tui_s.send(LibMpvEventMessage::PlaybackRestart(pause))?;
-                        mc_os_s.send(LibMpvEventMessage::PlaybackRestart(pause))?;
-                    }
-                    libmpv2::events::Event::PropertyChange {
-                        name: "pause",
-                        change: libmpv2::events::PropertyData::Flag(pause),
-                        ..
-                    } => {
-                        if pause {
-                            tui_s.send(LibMpvEventMessage::PlaybackPause)?;
-                            mc_os_s.send(LibMpvEventMessage::PlaybackPause)?;
-                        } else {
-                            tui_s.send(LibMpvEventMessage::PlaybackResume)?;
-                            mc_os_s.send(LibMpvEventMessage::PlaybackResume)?;
-                        }
-                    }
-                    libmpv2::events::Event::PropertyChange {
-                        name: "volume",
-                        change: libmpv2::events::PropertyData::Int64(volume),
-                        ..
-                    } => {
-                        tui_s.send(LibMpvEventMessage::VolumeUpdate(volume))?;
-                        mc_os_s.send(LibMpvEventMessage::VolumeUpdate(volume))?;
-                    }
-                    libmpv2::events::Event::PropertyChange {
-                        name: "duration/full",
-                        change: libmpv2::events::PropertyData::Double(duration),
-                        ..
-                    } => {
-                        tui_s.send(LibMpvEventMessage::DurationUpdate(duration))?;
-                        mc_os_s.send(LibMpvEventMessage::DurationUpdate(duration))?;
-                    }
-                    libmpv2::events::Event::PropertyChange {
-                        name: "playlist-playing-pos",
-                        change: libmpv2::events::PropertyData::Int64(pos),
-                        ..
-                    } => {
-                        if pos != -1 {
-                            tui_s.send(LibMpvEventMessage::PlaylistPosUpdate(pos))?;
-                            mc_os_s.send(LibMpvEventMessage::PlaylistPosUpdate(pos))?;
+        self.ignore_playnext_until_load = true;
+        self.send_connection_update(&tui_s)?;
+        tui_s.send(LibMpvEventMessage::ShuffleModeUpdate(self.shuffle_mode))?;
+        tui_s.send(LibMpvEventMessage::RepeatModeUpdate(self.repeat_mode))?;
+        mc_os_s.send(LibMpvEventMessage::RepeatModeUpdate(self.repeat_mode))?;
+
+        let (wakeup_s, wakeup_r) = crossbeam::channel::bounded(1);
+        mpv_client.set_wakeup_callback(move || {
+            let _ = wakeup_s.try_send(());
+        });
+        let network_r = self.network.receiver().clone();
+        let mut msg = None;
+        let mut response = None;
+        'run: loop {
+            while let Some(ev) = mpv_client.wait_event(0.0) {
+                match ev {
+                    Ok(event) => {
+                        log::debug!("Event {event:?}");
+                        if let Err(err) =
+                            self.handle_event(event, url, &library_cache, &tui_s, &mc_os_s)
+                        {
+                            self.report_error(err, &tui_s)?;
                         }
                     }
-                    libmpv2::events::Event::Seek => {
-                        let time_pos = self.mpv.get_property::<f64>("time-pos/full")?;
-                        tui_s.send(LibMpvEventMessage::PositionUpdate(time_pos))?;
-                        mc_os_s.send(LibMpvEventMessage::PositionUpdate(time_pos))?;
-                    }
-                    libmpv2::events::Event::FileLoaded => {
-                        let media_title = self
-                            .mpv
-                            .get_property::<libmpv2::MpvStr>("metadata/by-key/title")
-                            .or_else(|_| self.mpv.get_property::<libmpv2::MpvStr>("media-title"))?
-                            .to_string();
-                        let artist = self
-                            .mpv
-                            .get_property::<libmpv2::MpvStr>("metadata/by-key/artist")
-                            .map(|s| Some(s.to_string()))
-                            .unwrap_or_else(|_| None);
-                        let album = self
-                            .mpv
-                            .get_property::<libmpv2::MpvStr>("metadata/by-key/album")
-                            .map(|s| Some(s.to_string()))
-                            .unwrap_or_else(|_| None);
-
-                        let duration = self.mpv.get_property::<f64>("duration/full").unwrap_or(0.0);
-                        let volume = self.mpv.get_property::<i64>("volume")?;
-                        tui_s.send(LibMpvEventMessage::FileLoaded(FileLoadedData {
-                            media_title: media_title.clone(),
-                            artist: artist.clone(),
-                            album: album.clone(),
-                            duration,
-                            volume,
-                        }))?;
-                        mc_os_s.send(LibMpvEventMessage::FileLoaded(FileLoadedData {
-                            media_title,
-                            artist,
-                            album,
-                            duration,
-                            volume,
-                        }))?;
-                        ignore_playnext_until_load = false;
-                    }
-                    libmpv2::events::Event::EndFile(0) => {
-                        let audiofile = get_random_audiofile(url)?;
-                        let audiofile_url = audiofile_to_url(url, &audiofile);
-                        self.load_file(&audiofile_url)?;
-                    }
-
-                    _ => (),
-                },
-                Err(_err) => {
-                    //println!("ERR: {err:?}");
+                    Err(err) => log::debug!("Event: {err:?}"),
                 }
             }
 
-            if let Ok(msg) = libmpv_r.try_recv() {
+            for msg in msg.take().into_iter().chain(libmpv_r.try_iter()) {
                 log::debug!("LibMpvMessage: {msg:?}");
-                match msg {
-                    LibMpvMessage::Quit => {
-                        mc_os_s.send(LibMpvEventMessage::Quit)?;
-                        self.mpv.command("quit", &["0"])?;
+                match self.handle_message(msg, url, &library_cache, &tui_s, &mc_os_s) {
+                    Ok(true) => break 'run,
+                    Ok(false) => (),
+                    Err(err) => self.report_error(err, &tui_s)?,
+                }
+            }
+
+            for response in response.take().into_iter().chain(network_r.try_iter()) {
+                log::debug!("NetworkResponse: {response:?}");
+                if let Err(err) =
+                    self.handle_network_response(response, url, &library_cache, &tui_s)
+                {
+                    self.report_error(err, &tui_s)?;
+                }
+            }
+
+            self.retry_reconnect();
+
+            if let Some(timer) = self.timer
+                && timer.remaining().is_zero()
+            {
+                self.timer = None;
+                tui_s.send(LibMpvEventMessage::TimerUpdate(None))?;
+                match timer.action {
+                    TimerAction::Pause => self.mpv.set_property("pause", true)?,
+                    TimerAction::Quit => {
+                        self.quit(&tui_s, &mc_os_s)?;
                         break;
                     }
-                    LibMpvMessage::UpdateVolume(vol) => {
-                        let mut volume = self.mpv.get_property::<i64>("volume")?;
-                        volume += vol;
-                        volume = volume.clamp(0, 200);
-                        self.mpv.set_property("volume", volume)?;
-                    }
-                    LibMpvMessage::SetPosition(pos) => {
-                        self.mpv.command("seek", &[&pos.to_string(), "absolute"])?;
-                    }
-                    LibMpvMessage::SetVolume(vol) => {
-                        self.mpv.set_property("volume", vol)?;
+                }
+            }
+
+            let deadline = self
+                .reconnect
+                .as_ref()
+                .filter(|reconnect| !reconnect.pinging)
+                .map(|reconnect| reconnect.retry_at)
+                .into_iter()
+                .chain(self.timer.map(|timer| timer.deadline))
+                .min();
+            let timeout_r = match deadline {
+                Some(deadline) => crossbeam::channel::after(
+                    deadline
+                        .duration_since(std::time::SystemTime::now())
+                        .unwrap_or_default(),
+                ),
+                None => crossbeam::channel::never(),
+            };
+            crossbeam::channel::select! {
+                recv(wakeup_r) -> _ => (),
+                recv(libmpv_r) -> received => match received {
+                    Ok(received) => msg = Some(received),
+                    Err(_) => msg = Some(LibMpvMessage::Quit),
+                },
+                recv(network_r) -> received => response = received.ok(),
+                recv(timeout_r) -> _ => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle_event(
+        &mut self,
+        event: libmpv2::events::Event,
+        url: &str,
+        library_cache: &LibraryCache,
+        tui_s: &crossbeam::channel::Sender<LibMpvEventMessage>,
+        mc_os_s: &crossbeam::channel::Sender<LibMpvEventMessage>,
+    ) -> Result<(), SonicTunesError> {
+        match event {
+            libmpv2::events::Event::StartFile => {
+                self.playing_index = self
+                    .mpv
+                    .get_property::<i64>("playlist-playing-pos")
+                    .ok()
+                    .and_then(|pos| usize::try_from(pos).ok());
+                if self.playing_index.is_some()
+                    && self.playing_index == self.prefetched.as_ref().map(|p| p.index)
+                {
+                    self.prefetched = None;
+                }
+                self.state.ready = false;
+                self.state.position = 0.0;
+                self.broadcast_state(tui_s, mc_os_s)?;
+            }
+            libmpv2::events::Event::PlaybackRestart => {
+                self.state.ready = true;
+                self.state.paused = self.mpv.get_property::<bool>("pause")?;
+                self.state.position = self.mpv.get_property::<f64>("time-pos/full").unwrap_or(0.0);
+                self.broadcast_state(tui_s, mc_os_s)?;
+                self.capture_cover_art(tui_s, mc_os_s)?;
+            }
+            libmpv2::events::Event::VideoReconfig => {
+                self.capture_cover_art(tui_s, mc_os_s)?;
+            }
+            libmpv2::events::Event::PropertyChange {
+                name: "pause",
+                change: libmpv2::events::PropertyData::Flag(pause),
+                ..
+            } => {
+                self.state.paused = pause;
+                self.broadcast_state(tui_s, mc_os_s)?;
+            }
+            libmpv2::events::Event::PropertyChange {
+                name: "volume",
+                change: libmpv2::events::PropertyData::Int64(volume),
+                ..
+            } => {
+                self.state.volume = volume;
+                self.broadcast_state(tui_s, mc_os_s)?;
+            }
+            libmpv2::events::Event::PropertyChange {
+                name: "duration/full",
+                change: libmpv2::events::PropertyData::Double(duration),
+                ..
+            } => {
+                self.state.duration = duration;
+                self.broadcast_state(tui_s, mc_os_s)?;
+            }
+            libmpv2::events::Event::PropertyChange {
+                name: "playlist-playing-pos",
+                change: libmpv2::events::PropertyData::Int64(pos),
+                ..
+            } => {
+                if pos != -1 {
+                    tui_s.send(LibMpvEventMessage::PlaylistPosUpdate(pos))?;
+                    mc_os_s.send(LibMpvEventMessage::PlaylistPosUpdate(pos))?;
+                }
+            }
+            libmpv2::events::Event::PropertyChange {
+                name: "time-pos",
+                change: libmpv2::events::PropertyData::Double(time_pos),
+                ..
+            } => {
+                // Consumers extrapolate between updates, so only resync them once per second.
+                let resync = time_pos.trunc() != self.state.position.trunc();
+                self.state.position = time_pos;
+                if resync {
+                    self.broadcast_state(tui_s, mc_os_s)?;
+                } else {
+                    self.state.updated_at = std::time::SystemTime::now();
+                }
+            }
+            libmpv2::events::Event::Seek => {
+                self.state.position = self.mpv.get_property::<f64>("time-pos/full")?;
+                self.broadcast_state(tui_s, mc_os_s)?;
+            }
+            libmpv2::events::Event::FileLoaded => {
+                let media_title = self
+                    .mpv
+                    .get_property::<libmpv2::MpvStr>("metadata/by-key/title")
+                    .or_else(|_| self.mpv.get_property::<libmpv2::MpvStr>("media-title"))?
+                    .to_string();
+                let artist = self
+                    .mpv
+                    .get_property::<libmpv2::MpvStr>("metadata/by-key/artist")
+                    .map(|s| Some(s.to_string()))
+                    .unwrap_or_else(|_| None);
+                let album = self
+                    .mpv
+                    .get_property::<libmpv2::MpvStr>("metadata/by-key/album")
+                    .map(|s| Some(s.to_string()))
+                    .unwrap_or_else(|_| None);
+
+                let pos = self.mpv.get_property::<i64>("playlist-playing-pos")?;
+                let audiofile = usize::try_from(pos)
+                    .ok()
+                    .and_then(|pos| self.playlist.get(pos))
+                    .cloned();
+                self.finish_history_entry(true);
+                self.history_entry = audiofile.as_ref().map(|audiofile| {
+                    HistoryEntry::new(audiofile, &media_title, artist.as_deref(), album.as_deref())
+                });
+                self.state.position = 0.0;
+                if let Some(ref audiofile) = audiofile
+                    && !self.offline
+                {
+                    self.audio_cache.played(url, audiofile);
+                }
+                if let Some(position) = self.resume_position.take() {
+                    self.mpv
+                        .command("seek", &[&position.to_string(), "absolute"])?;
+                }
+                tui_s.send(LibMpvEventMessage::FileLoaded(Box::new(FileLoadedData {
+                    media_title: media_title.clone(),
+                    artist: artist.clone(),
+                    album: album.clone(),
+                    audiofile: audiofile.clone(),
+                })))?;
+                mc_os_s.send(LibMpvEventMessage::FileLoaded(Box::new(FileLoadedData {
+                    media_title,
+                    artist,
+                    album,
+                    audiofile: audiofile.clone(),
+                })))?;
+                if self.cover_art.is_some() {
+                    self.remove_cover_art();
+                    tui_s.send(LibMpvEventMessage::CoverArtUpdate(None))?;
+                    mc_os_s.send(LibMpvEventMessage::CoverArtUpdate(None))?;
+                }
+                self.cover_art_pending =
+                    self.load_cover_art(url, library_cache, audiofile.as_ref());
+                self.load_lyrics(library_cache, audiofile.as_ref());
+                tui_s.send(LibMpvEventMessage::LyricsUpdate(self.lyrics.clone()))?;
+                self.ignore_playnext_until_load = false;
+                self.prefetch_next(url, library_cache, tui_s)?;
+            }
+            libmpv2::events::Event::EndFile(0) => {
+                self.finish_history_entry(false);
+                match self.repeat_mode {
+                    RepeatMode::Off if self.prefetched.is_some() => (),
+                    RepeatMode::Off => {
+                        self.load_next_file_or_reconnect(url, library_cache, tui_s)?;
+                        self.send_queue_update(tui_s)?;
                     }
-                    LibMpvMessage::UpdatePosition(offset) => {
-                        self.mpv.command("seek", &[&offset.to_string()])?;
+                    RepeatMode::All => {
+                        if let Some(audiofile) = self.queue.pop_front() {
+                            self.load_file(url, audiofile)?;
+                            self.send_queue_update(tui_s)?;
+                        }
                     }
-                    LibMpvMessage::PlayPause => {
-                        self.mpv.command("cycle", &["pause"])?;
+                    RepeatMode::One | RepeatMode::Stop => (),
+                }
+            }
+            libmpv2::events::Event::EndFile(2) => {
+                self.finish_history_entry(true);
+            }
+            // MPV_END_FILE_REASON_ERROR, e.g. the server dropped the connection.
+            libmpv2::events::Event::EndFile(4) => {
+                self.history_entry = None;
+                self.network
+                    .ping(PingReason::EndFile(self.playing_index, self.state.position));
+            }
+
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    fn handle_network_response(
+        &mut self,
+        response: NetworkResponse,
+        url: &str,
+        library_cache: &LibraryCache,
+        tui_s: &crossbeam::channel::Sender<LibMpvEventMessage>,
+    ) -> Result<(), SonicTunesError> {
+        match response {
+            NetworkResponse::Ping(PingReason::Reconnect, result) => {
+                self.finish_reconnect(result, url, library_cache, tui_s)?;
+            }
+            NetworkResponse::Ping(PingReason::EndFile(index, position), result) => {
+                // mpv moves on to the next playlist entry by itself, e.g. a prefetched one.
+                let idle = self.mpv.get_property::<bool>("idle-active")?;
+                if let Err(err) = result {
+                    log::error!("EndFile: {err}");
+                    if self.enter_offline_mode() {
+                        if idle {
+                            self.load_next_file_or_reconnect(url, library_cache, tui_s)?;
+                            self.send_queue_update(tui_s)?;
+                        }
+                        self.send_connection_update(tui_s)?;
+                    } else if let Some(index) = index
+                        && self.reconnect.is_none()
+                    {
+                        self.schedule_reconnect(ReconnectAction::Reload(index, position), 0);
+                        self.send_connection_update(tui_s)?;
                     }
-                    LibMpvMessage::Resume => {
-                        self.mpv.set_property("pause", false)?;
+                } else if self.repeat_mode == RepeatMode::Off && idle {
+                    log::error!("EndFile: unplayable file, skipping");
+                    self.load_next_file_or_reconnect(url, library_cache, tui_s)?;
+                    self.send_queue_update(tui_s)?;
+                }
+            }
+            NetworkResponse::LibraryRefreshed(force, result) => {
+                self.refreshing_library = false;
+                match result {
+                    Ok(()) => {
+                        if let Some(ref mut reconnect) = self.reconnect
+                            && matches!(reconnect.action, ReconnectAction::LoadNext)
+                            && !reconnect.pinging
+                        {
+                            reconnect.retry_at = std::time::SystemTime::now();
+                        }
+                        self.prefetch_next(url, library_cache, tui_s)?;
+                        tui_s.send(LibMpvEventMessage::LibraryRefreshed)?;
                     }
-                    LibMpvMessage::Pause => {
-                        self.mpv.set_property("pause", true)?;
+                    Err(err) if force => {
+                        log::error!("LibraryCache: {err}");
+                        tui_s.send(LibMpvEventMessage::Error(
+                            "library refresh failed".to_string(),
+                        ))?;
                     }
-                    LibMpvMessage::Stop => {
-                        self.mpv.set_property("pause", true)?;
-                        self.mpv.command("seek", &["0.0", "absolute"])?;
+                    Err(err) => log::error!("LibraryCache: {err}"),
+                }
+            }
+            NetworkResponse::Lyrics(id, result) => {
+                if self.lyrics_pending.as_ref() != Some(&id) {
+                    return Ok(());
+                }
+                self.lyrics_pending = None;
+                match result {
+                    Ok(lyrics) => {
+                        self.lyrics = Some(lyrics);
+                        tui_s.send(LibMpvEventMessage::LyricsUpdate(self.lyrics.clone()))?;
                     }
-                    LibMpvMessage::PlayNext => {
-                        if !ignore_playnext_until_load {
-                            if let Err(err) = self.mpv.command("playlist-next", &["weak"]) {
-                                ignore_playnext_until_load = true;
-                                if err != libmpv2::Error::Raw(-12) {
-                                    panic!("{err:?}");
-                                } else {
-                                    let pos =
-                                        self.mpv.get_property::<i64>("playlist-playing-pos")?;
-                                    if pos != -1 {
-                                        let count =
-                                            self.mpv.get_property::<i64>("playlist-count")?;
-                                        if pos == count - 1 {
-                                            let audiofile = get_random_audiofile(url)?;
-                                            let audiofile_url = audiofile_to_url(url, &audiofile);
-                                            self.load_file(&audiofile_url)?;
-                                        }
-                                        self.mpv.command("playlist-next", &["weak"])?;
-                                    }
+                    Err(err) => log::error!("Lyrics: {err}"),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle_message(
+        &mut self,
+        msg: LibMpvMessage,
+        url: &str,
+        library_cache: &LibraryCache,
+        tui_s: &crossbeam::channel::Sender<LibMpvEventMessage>,
+        mc_os_s: &crossbeam::channel::Sender<LibMpvEventMessage>,
+    ) -> Result<bool, SonicTunesError> {
+        match msg {
+            LibMpvMessage::Quit => {
+                self.quit(tui_s, mc_os_s)?;
+                return Ok(true);
+            }
+            LibMpvMessage::UpdateVolume(vol) => {
+                let mut volume = self.mpv.get_property::<i64>("volume")?;
+                volume += vol;
+                volume = volume.clamp(0, 200);
+                self.mpv.set_property("volume", volume)?;
+            }
+            LibMpvMessage::SetPosition(pos) => {
+                self.mpv.command("seek", &[&pos.to_string(), "absolute"])?;
+            }
+            LibMpvMessage::SetVolume(vol) => {
+                self.mpv.set_property("volume", vol)?;
+            }
+            LibMpvMessage::UpdatePosition(offset) => {
+                self.mpv.command("seek", &[&offset.to_string()])?;
+            }
+            LibMpvMessage::PlayPause => {
+                self.mpv.command("cycle", &["pause"])?;
+            }
+            LibMpvMessage::Resume => {
+                self.mpv.set_property("pause", false)?;
+            }
+            LibMpvMessage::Pause => {
+                self.mpv.set_property("pause", true)?;
+            }
+            LibMpvMessage::Stop => {
+                self.mpv.set_property("pause", true)?;
+                self.mpv.command("seek", &["0.0", "absolute"])?;
+            }
+            LibMpvMessage::PlayNext => {
+                if !self.ignore_playnext_until_load {
+                    if let Err(err) = self.mpv.command("playlist-next", &["weak"]) {
+                        self.ignore_playnext_until_load = true;
+                        if err != libmpv2::Error::Raw(-12) {
+                            self.ignore_playnext_until_load = false;
+                            return Err(err.into());
+                        } else {
+                            let pos = self.mpv.get_property::<i64>("playlist-playing-pos")?;
+                            if pos != -1 {
+                                let count = self.mpv.get_property::<i64>("playlist-count")?;
+                                let loaded = pos != count - 1
+                                    || self.load_next_file_or_reconnect(
+                                        url,
+                                        library_cache,
+                                        tui_s,
+                                    )?;
+                                if loaded {
+                                    self.send_queue_update(tui_s)?;
+                                    self.mpv.command("playlist-next", &["weak"])?;
                                 }
                             }
-                        } else {
-                            log::debug!("LibMpvMessage::PlayNext: ignored");
                         }
                     }
-                    LibMpvMessage::PlayPrevious => {
-                        if let Err(err) = self.mpv.command("playlist-prev", &["weak"]) {
-                            if err != libmpv2::Error::Raw(-12) {
-                                panic!("{err:?}");
-                            } else {
-                                self.mpv.command("seek", &["0", "absolute"])?;
-                            }
-                        }
+                } else {
+                    log::debug!("LibMpvMessage::PlayNext: ignored");
+                }
+            }
+            LibMpvMessage::PlayPrevious => {
+                if let Err(err) = self.mpv.command("playlist-prev", &["weak"]) {
+                    if err != libmpv2::Error::Raw(-12) {
+                        return Err(err.into());
+                    } else {
+                        self.mpv.command("seek", &["0", "absolute"])?;
+                    }
+                }
+            }
+            LibMpvMessage::Play(mut audiofiles) => {
+                // Remote commands and history entries may name files that are not audio.
+                audiofiles.retain(AudioFile::is_playable);
+                if !audiofiles.is_empty() {
+                    self.cancel_prefetch()?;
+                    let audiofile = audiofiles.remove(0);
+                    let count = self.mpv.get_property::<i64>("playlist-count")?;
+                    self.append_file(url, audiofile)?;
+                    self.mpv
+                        .command("playlist-play-index", &[&count.to_string()])?;
+                    for audiofile in audiofiles.into_iter().rev() {
+                        self.queue.push_front(audiofile);
                     }
+                    self.send_queue_update(tui_s)?;
                 }
             }
+            LibMpvMessage::PlayIndex(i) => {
+                self.mpv.command("playlist-play-index", &[&i.to_string()])?;
+            }
+            LibMpvMessage::RemoveIndex(i) => {
+                if i < self.playlist.len() {
+                    self.mpv.command("playlist-remove", &[&i.to_string()])?;
+                    self.playlist.remove(i);
+                    if let Some(ref mut prefetched) = self.prefetched
+                        && i < prefetched.index
+                    {
+                        prefetched.index -= 1;
+                    }
+                }
+            }
+            LibMpvMessage::Enqueue(mut audiofiles) => {
+                audiofiles.retain(AudioFile::is_playable);
+                self.cancel_prefetch_from(false)?;
+                self.queue.extend(audiofiles);
+                self.send_queue_update(tui_s)?;
+                self.prefetch_next(url, library_cache, tui_s)?;
+            }
+            LibMpvMessage::EnqueueNext(mut audiofiles) => {
+                audiofiles.retain(AudioFile::is_playable);
+                self.cancel_prefetch()?;
+                for audiofile in audiofiles.into_iter().rev() {
+                    self.queue.push_front(audiofile);
+                }
+                self.send_queue_update(tui_s)?;
+                self.prefetch_next(url, library_cache, tui_s)?;
+            }
+            LibMpvMessage::Dequeue(i) => {
+                if let Some(i) = self.queue_index(i, i == 0)?
+                    && self.queue.remove(i).is_some()
+                {
+                    self.send_queue_update(tui_s)?;
+                }
+                self.prefetch_next(url, library_cache, tui_s)?;
+            }
+            LibMpvMessage::QueueMoveUp(i) => {
+                if let Some(i) = self.queue_index(i, i == 1)?
+                    && i > 0
+                    && i < self.queue.len()
+                {
+                    self.queue.swap(i, i - 1);
+                    self.send_queue_update(tui_s)?;
+                }
+                self.prefetch_next(url, library_cache, tui_s)?;
+            }
+            LibMpvMessage::QueueMoveDown(i) => {
+                if let Some(i) = self.queue_index(i, i == 0)?
+                    && i + 1 < self.queue.len()
+                {
+                    self.queue.swap(i, i + 1);
+                    self.send_queue_update(tui_s)?;
+                }
+                self.prefetch_next(url, library_cache, tui_s)?;
+            }
+            LibMpvMessage::PauseAfter(min) => {
+                self.set_timer(TimerAction::Pause, min, tui_s)?;
+            }
+            LibMpvMessage::QuitAfter(min) => {
+                self.set_timer(TimerAction::Quit, min, tui_s)?;
+            }
+            LibMpvMessage::Download(audiofiles) => {
+                self.audio_cache.download(url, audiofiles, true);
+            }
+            LibMpvMessage::RefreshLibrary => {
+                self.refreshing_library = true;
+                self.network.refresh_library(true);
+            }
+            LibMpvMessage::Resync(state_s, event_s) => {
+                if let Err(err) = self.send_state(&state_s) {
+                    log::error!("Resync: {err:?}");
+                }
+                // Events sent before this point are covered by the snapshot.
+                tui_s.send(LibMpvEventMessage::Subscribe(event_s))?;
+            }
+            LibMpvMessage::Status(status_s) => {
+                let _ = status_s.send(self.player_status()?);
+            }
+            LibMpvMessage::ClearQueue => {
+                self.cancel_prefetch_from(true)?;
+                self.queue.clear();
+                self.send_queue_update(tui_s)?;
+                self.prefetch_next(url, library_cache, tui_s)?;
+            }
+            LibMpvMessage::SetShuffleMode(shuffle_mode) => {
+                self.cancel_prefetch_from(false)?;
+                self.shuffle_mode = shuffle_mode;
+                tui_s.send(LibMpvEventMessage::ShuffleModeUpdate(shuffle_mode))?;
+                self.prefetch_next(url, library_cache, tui_s)?;
+            }
+            LibMpvMessage::SetRepeatMode(repeat_mode) => {
+                self.cancel_prefetch()?;
+                self.set_repeat_mode(repeat_mode)?;
+                tui_s.send(LibMpvEventMessage::RepeatModeUpdate(repeat_mode))?;
+                mc_os_s.send(LibMpvEventMessage::RepeatModeUpdate(repeat_mode))?;
+                self.send_queue_update(tui_s)?;
+                self.prefetch_next(url, library_cache, tui_s)?;
+            }
+            LibMpvMessage::CycleRepeatMode => {
+                self.cancel_prefetch()?;
+                let repeat_mode = self.repeat_mode.next();
+                self.set_repeat_mode(repeat_mode)?;
+                tui_s.send(LibMpvEventMessage::RepeatModeUpdate(repeat_mode))?;
+                mc_os_s.send(LibMpvEventMessage::RepeatModeUpdate(repeat_mode))?;
+                self.send_queue_update(tui_s)?;
+                self.prefetch_next(url, library_cache, tui_s)?;
+            }
         }
 
+        Ok(false)
+    }
+
+    fn report_error(
+        &self,
+        err: SonicTunesError,
+        tui_s: &crossbeam::channel::Sender<LibMpvEventMessage>,
+    ) -> Result<(), SonicTunesError> {
+        if let SonicTunesError::LibMpvEventMessageSendError(_) = err {
+            return Err(err);
+        }
+        log::error!("MpvHandler: {err:?}");
+        tui_s.send(LibMpvEventMessage::Error(err.to_string()))?;
+
+        Ok(())
+    }
+
+    fn quit(
+        &mut self,
+        tui_s: &crossbeam::channel::Sender<LibMpvEventMessage>,
+        mc_os_s: &crossbeam::channel::Sender<LibMpvEventMessage>,
+    ) -> Result<(), SonicTunesError> {
+        self.finish_history_entry(false);
+        self.remove_cover_art();
+        self.audio_cache.save();
+        if let Err(err) = self.shuffle_bag.save() {
+            log::error!("ShuffleBag: {err:?}");
+        }
+        mc_os_s.send(LibMpvEventMessage::Quit)?;
+        // The TUI is already gone when it requested the quit itself.
+        let _ = tui_s.send(LibMpvEventMessage::Quit);
+        self.mpv.command("quit", &["0"])?;
+
         Ok(())
     }
+
+    fn set_timer(
+        &mut self,
+        action: TimerAction,
+        min: u64,
+        tui_s: &crossbeam::channel::Sender<LibMpvEventMessage>,
+    ) -> Result<(), SonicTunesError> {
+        self.timer = Some(PlaybackTimer {
+            action,
+            deadline: std::time::SystemTime::now() + std::time::Duration::from_mins(min),
+        });
+        tui_s.send(LibMpvEventMessage::TimerUpdate(self.timer))?;
+
+        Ok(())
+    }
+}
+
+fn find_sidecar_cover(audiofiles: &[AudioFile], audiofile: &AudioFile) -> Option<AudioFile> {
+    let dir = audiofile
+        .path
+        .rsplit_once(['/', '\\'])
+        .map_or("", |(dir, _)| dir);
+
+    audiofiles
+        .iter()
+        .find(|other| {
+            let (other_dir, name) = other
+                .path
+                .rsplit_once(['/', '\\'])
+                .unwrap_or(("", &other.path));
+            let stem = name.rsplit_once('.').map_or(name, |(stem, _)| stem);
+            other.mime.starts_with("image/")
+                && other_dir == dir
+                && ["cover", "folder", "front"].contains(&stem.to_lowercase().as_str())
+        })
+        .cloned()
+}
+
+fn find_sidecar_lyrics(audiofiles: &[AudioFile], audiofile: &AudioFile) -> Option<AudioFile> {
+    let stem = audiofile
+        .path
+        .rsplit_once('.')
+        .map_or(audiofile.path.as_str(), |(stem, _)| stem);
+
+    audiofiles
+        .iter()
+        .find(|other| {
+            other
+                .path
+                .rsplit_once('.')
+                .is_some_and(|(other_stem, ext)| {
+                    other_stem == stem && ext.eq_ignore_ascii_case("lrc")
+                })
+        })
+        .cloned()
 }
@@ -0,0 +1,193 @@
+use crate::{AudioFile, SonicTunesError, get_cache_dir_path, get_reqwest_client};
+use rand::random_range;
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct LibraryCacheFile {
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    audiofiles: Vec<AudioFile>,
+}
+
+struct LibraryCacheState {
+    // Everything the server lists, sidecar covers and lyrics included.
+    files: std::sync::Arc<Vec<AudioFile>>,
+    audiofiles: std::sync::Arc<Vec<AudioFile>>,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    fetched: Option<std::time::SystemTime>,
+    generation: u64,
+}
+
+impl LibraryCacheState {
+    fn set_files(&mut self, files: Vec<AudioFile>) {
+        self.audiofiles = std::sync::Arc::new(
+            files
+                .iter()
+                .filter(|file| file.is_playable())
+                .cloned()
+                .collect(),
+        );
+        self.files = std::sync::Arc::new(files);
+    }
+}
+
+#[derive(Clone)]
+pub struct LibraryCache {
+    url: String,
+    disk_cache: bool,
+    refresh_interval: Option<std::time::Duration>,
+    state: std::sync::Arc<std::sync::RwLock<LibraryCacheState>>,
+}
+
+impl LibraryCache {
+    pub fn new(url: &str, disk_cache: bool, refresh_interval: Option<std::time::Duration>) -> Self {
+        let mut state = LibraryCacheState {
+            files: std::sync::Arc::new(vec![]),
+            audiofiles: std::sync::Arc::new(vec![]),
+            etag: None,
+            last_modified: None,
+            fetched: None,
+            generation: 0,
+        };
+
+        if disk_cache
+            && let Some(cache_file) = load_cache_file()
+            && cache_file.url == url
+        {
+            log::debug!("LibraryCache: loaded {} files", cache_file.audiofiles.len());
+            state.set_files(cache_file.audiofiles);
+            state.etag = cache_file.etag;
+            state.last_modified = cache_file.last_modified;
+        }
+
+        LibraryCache {
+            url: url.to_string(),
+            disk_cache,
+            refresh_interval,
+            state: std::sync::Arc::new(std::sync::RwLock::new(state)),
+        }
+    }
+
+    // Only the playable files, see AudioFile::is_playable.
+    pub fn audiofiles(&self) -> std::sync::Arc<Vec<AudioFile>> {
+        self.state.read().unwrap().audiofiles.clone()
+    }
+
+    pub fn files(&self) -> std::sync::Arc<Vec<AudioFile>> {
+        self.state.read().unwrap().files.clone()
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.state.read().unwrap().generation
+    }
+
+    pub fn refresh(&self) -> Result<bool, SonicTunesError> {
+        let (etag, last_modified) = {
+            let state = self.state.read().unwrap();
+            (state.etag.clone(), state.last_modified.clone())
+        };
+
+        let reqwest_client = get_reqwest_client()?;
+        let mut request = reqwest_client.get(format!("{}/files", self.url.trim_end_matches('/')));
+        if let Some(etag) = etag {
+            request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = last_modified {
+            request = request.header(reqwest::header::IF_MODIFIED_SINCE, last_modified);
+        }
+        let response = request.send()?;
+
+        if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+            log::debug!("LibraryCache: not modified");
+            self.state.write().unwrap().fetched = Some(std::time::SystemTime::now());
+            return Ok(false);
+        }
+
+        let response = response.error_for_status()?;
+        let header_to_string = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value: &reqwest::header::HeaderValue| value.to_str().ok())
+                .map(|value| value.to_string())
+        };
+        let etag = header_to_string(reqwest::header::ETAG);
+        let last_modified = header_to_string(reqwest::header::LAST_MODIFIED);
+        let audiofiles = response.json::<Vec<AudioFile>>()?;
+        log::debug!("LibraryCache: fetched {} files", audiofiles.len());
+
+        let cache_file = LibraryCacheFile {
+            url: self.url.clone(),
+            etag,
+            last_modified,
+            audiofiles,
+        };
+        if self.disk_cache
+            && let Err(err) = save_cache_file(&cache_file)
+        {
+            log::error!("LibraryCache: {err:?}");
+        }
+
+        let mut state = self.state.write().unwrap();
+        state.set_files(cache_file.audiofiles);
+        state.etag = cache_file.etag;
+        state.last_modified = cache_file.last_modified;
+        state.fetched = Some(std::time::SystemTime::now());
+        state.generation += 1;
+
+        Ok(true)
+    }
+
+    pub fn is_stale(&self) -> bool {
+        let state = self.state.read().unwrap();
+        match (state.fetched, self.refresh_interval) {
+            (None, _) => true,
+            (Some(fetched), Some(refresh_interval)) => fetched
+                .elapsed()
+                .map_or(true, |elapsed| elapsed >= refresh_interval),
+            (Some(_), None) => false,
+        }
+    }
+
+    pub fn refresh_if_stale(&self) -> Result<(), SonicTunesError> {
+        if self.is_stale()
+            && let Err(err) = self.refresh()
+        {
+            if self.audiofiles().is_empty() {
+                return Err(err);
+            }
+            log::error!("LibraryCache: {err:?}");
+        }
+
+        Ok(())
+    }
+
+    pub fn random_audiofile(&self) -> Result<AudioFile, SonicTunesError> {
+        let audiofiles = self.audiofiles();
+        if audiofiles.is_empty() {
+            return Err(SonicTunesError::EmptyLibrary);
+        }
+        let id = random_range(0..audiofiles.len());
+
+        Ok(audiofiles[id].clone())
+    }
+}
+
+fn load_cache_file() -> Option<LibraryCacheFile> {
+    let path = format!("{}/files.json", get_cache_dir_path()?);
+    let cache_file = std::fs::read_to_string(path).ok()?;
+
+    serde_json::from_str(&cache_file).ok()
+}
+
+fn save_cache_file(cache_file: &LibraryCacheFile) -> Result<(), SonicTunesError> {
+    let Some(dir_path) = get_cache_dir_path() else {
+        return Ok(());
+    };
+    std::fs::create_dir_all(&dir_path)?;
+    let cache_file = serde_json::to_string(cache_file)?;
+    std::fs::write(format!("{dir_path}/files.json"), cache_file)?;
+
+    Ok(())
+}
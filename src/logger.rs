@@ -18,25 +18,27 @@ impl LogSender {
 
     pub fn send_log_message(&self, msg: String) {
         let send = &self.sender;
-        send.send(LogMessage::Message(msg)).unwrap();
+        let _ = send.send(LogMessage::Message(msg));
     }
 
     pub fn send_quit_signal(&self) {
         let send = &self.sender;
-        send.send(LogMessage::Quit).unwrap();
+        let _ = send.send(LogMessage::Quit);
     }
 }
 
 pub struct Logger {
+    log_path: String,
     logger_signal_recv: crossbeam::channel::Receiver<LogMessage>,
     logger_signal_send: crossbeam::channel::Sender<LogMessage>,
 }
 
 impl Logger {
-    pub fn new() -> Self {
+    pub fn new(log_path: &str) -> Self {
         let (s, r) = crossbeam::channel::unbounded();
 
         Self {
+            log_path: log_path.to_string(),
             logger_signal_recv: r,
             logger_signal_send: s,
         }
@@ -59,19 +61,19 @@ impl Logger {
     }
 
     fn log_to_file(&self, message: &str) {
-        let mut log_file = std::fs::OpenOptions::new()
+        let log_file = std::fs::OpenOptions::new()
             .append(true)
             .create(true)
-            .open("debug.log")
-            .unwrap();
+            .open(&self.log_path);
 
-        writeln!(log_file, "{}", message).unwrap();
+        if let Ok(mut log_file) = log_file {
+            let _ = writeln!(log_file, "{}", message);
+        }
     }
 
     pub fn flush(&self) {
         let recv = &self.logger_signal_recv;
-        for _i in 0..recv.len() {
-            let signal = recv.recv().unwrap();
+        while let Ok(signal) = recv.try_recv() {
             if let LogMessage::Message(msg) = signal {
                 self.log_to_file(&msg)
             }
@@ -1,7 +1,13 @@
 use sonictunes::{
-    PingResponse, ProgramOption, audiofile_to_url, get_random_audiofile,
+    ProgramOption, SonicTunesError,
+    audio_cache::AudioCache,
+    config::{Config, save_url_to_config},
     libmpv_handler::{LibMpvEventMessage, LibMpvHandler, LibMpvMessage},
-    print_help, process_args, reqwest_get, save_url_to_config,
+    library_cache::LibraryCache,
+    network::NetworkWorker,
+    ping_server, print_help, process_args, set_reqwest_timeout,
+    shuffle::ShuffleBag,
+    tui::{cover_art::AlbumArtMode, keybindings::Keybindings, theme::Themes},
 };
 
 fn main() {
@@ -13,7 +19,7 @@ fn main() {
                     eprintln!("Provided option {option} is invalid")
                 }
                 sonictunes::SonicTunesError::InvalidOptionsStructure => eprintln!("Invalid input"),
-                _ => panic!("{:?}", err),
+                err => eprintln!("{err}"),
             }
             print_help();
             std::process::exit(-1);
@@ -24,8 +30,65 @@ fn main() {
         std::process::exit(-1);
     }
 
-    if options.contains(&ProgramOption::Verbose) {
-        let logger = sonictunes::logger::Logger::new();
+    if let Some(command) = options.iter().find_map(|o| match o {
+        ProgramOption::Remote(command) => Some(command),
+        _ => None,
+    }) {
+        match sonictunes::ipc::send_remote_command(command) {
+            Ok(response) => {
+                print!("{response}");
+                if response.starts_with("error") {
+                    std::process::exit(-1);
+                }
+                std::process::exit(0);
+            }
+            Err(err) => {
+                eprintln!("Connection to running instance failed, {err:?}");
+                std::process::exit(-1);
+            }
+        }
+    }
+
+    let status_format = options.iter().find_map(|o| match o {
+        ProgramOption::StatusFormat(format) => Some(format.as_str()),
+        _ => None,
+    });
+    let status_json = options.contains(&ProgramOption::StatusJson);
+    if status_format.is_some() || status_json {
+        let format = status_format.unwrap_or("{artist} - {title}");
+        // Print an empty line when no instance is running, so status bars hide the module.
+        let status_str = match sonictunes::ipc::query_status() {
+            Ok(status) if status_json => status.generate_waybar_json(format).unwrap_or_default(),
+            Ok(status) => status.format(format),
+            Err(err) => {
+                log::debug!("Status: {err:?}");
+                String::new()
+            }
+        };
+        println!("{status_str}");
+        std::process::exit(0);
+    }
+
+    let config_path = options.iter().find_map(|o| match o {
+        ProgramOption::ConfigPath(path) => Some(path.as_str()),
+        _ => None,
+    });
+    let mut config = Config::load(config_path)
+        .map_err(|err| {
+            match err {
+                sonictunes::SonicTunesError::ConfigError(err) => {
+                    eprintln!("Invalid config file {err}")
+                }
+                _ => eprintln!("Failed to load config file: {err:?}"),
+            }
+            std::process::exit(-1);
+        })
+        .unwrap();
+    config.apply_options(&options);
+    set_reqwest_timeout(std::time::Duration::from_secs(config.timeout));
+
+    if config.verbose {
+        let logger = sonictunes::logger::Logger::new(&config.log_path);
         log_send = Some(sonictunes::logger::LogSender::new(logger.get_signal_send()));
         log::set_boxed_logger(Box::new(log_send.as_ref().unwrap().clone())).unwrap();
         log::set_max_level(log::LevelFilter::Trace);
@@ -35,106 +98,389 @@ fn main() {
             logger.flush();
         });
         log::debug!("Args: {:?}", std::env::args());
+        log::debug!("Config: {:?}", config);
     }
 
-    let volume = if let Some(vol) = options.iter().find_map(|o| match o {
-        ProgramOption::Volume(vol) => Some(*vol),
-        _ => None,
-    }) {
-        vol
-    } else {
-        50
-    };
+    let keybindings = Keybindings::new(&config.keybindings)
+        .map_err(|err| {
+            if let sonictunes::SonicTunesError::ConfigError(err) = err {
+                eprintln!("Invalid config file {err}");
+            }
+            std::process::exit(-1);
+        })
+        .unwrap();
+    let themes = Themes::new(config.theme.as_deref(), &config.themes)
+        .map_err(|err| {
+            if let sonictunes::SonicTunesError::ConfigError(err) = err {
+                eprintln!("Invalid config file {err}");
+            }
+            std::process::exit(-1);
+        })
+        .unwrap();
+    let album_art = config.album_art.resolve();
 
-    let url = options.iter().find_map(|o| match o {
-        ProgramOption::URL(url) => Some(url.to_owned()),
-        _ => None,
-    });
-    if url.is_none() {
+    let no_tui = options.contains(&ProgramOption::NoTui);
+    if options.contains(&ProgramOption::Daemon) {
+        start_daemon();
+    }
+    if !no_tui && let Ok((attach_info, attached_stream)) = sonictunes::ipc::attach() {
+        log::debug!("Attach: {}", attach_info.url);
+        let errors = attach_tui(
+            &attach_info.url,
+            attached_stream,
+            &config,
+            keybindings,
+            themes,
+            album_art,
+        );
+        if let Some(log_send) = log_send {
+            log_send.send_quit_signal();
+        }
+        exit_on_errors(&errors);
+        return;
+    }
+    if no_tui && sonictunes::ipc::send_remote_command("status").is_ok() {
+        eprintln!("Another instance is already running");
+        std::process::exit(-1);
+    }
+
+    let urls = config.server_urls();
+    if urls.is_empty() {
         eprintln!("Invalid input");
         print_help();
         std::process::exit(-1);
     }
-    let url = url.unwrap();
-    log::debug!("URL: {:?}", url);
 
-    if let Ok(response) = reqwest_get(&format!("{}/ping", url.trim_end_matches('/'))) {
-        let ping_response: PingResponse = response
-            .json()
-            .map_err(|err| eprintln!("Invalid server response, {err}"))
-            .unwrap();
-        if ping_response.status != "ok" {
-            eprintln!("Invalid server status, {}", ping_response.status);
-            std::process::exit(-1);
+    let mut url = None;
+    for server_url in urls {
+        log::debug!("URL: {:?}", server_url);
+        match ping_server(&server_url) {
+            Ok(()) => {
+                url = Some(server_url);
+                break;
+            }
+            Err(err @ SonicTunesError::ServerStatus(_)) => {
+                eprintln!("{err}");
+                std::process::exit(-1);
+            }
+            Err(err) => log::error!("Ping {server_url}: {err:?}"),
         }
-    } else {
+    }
+    let audio_cache = AudioCache::new(
+        config.cache.audio_max_size * 1024 * 1024,
+        config.cache.enabled && config.cache.audio,
+    );
+    let offline = url.is_none();
+    let Some(url) = url.or_else(|| {
+        config
+            .server_urls()
+            .into_iter()
+            .next()
+            .filter(|_| !audio_cache.is_empty())
+    }) else {
         eprintln!("Connection to server failed");
         std::process::exit(-1);
+    };
+
+    if !offline && let Err(err) = save_url_to_config(config_path, &url) {
+        log::error!("Config: {err:?}");
     }
 
-    save_url_to_config(&url);
+    let library_cache = LibraryCache::new(
+        &url,
+        config.cache.enabled,
+        config
+            .cache
+            .refresh_interval
+            .map(std::time::Duration::from_mins),
+    );
+    if !offline
+        && library_cache.audiofiles().is_empty()
+        && let Err(err) = library_cache.refresh_if_stale()
+    {
+        log::error!("LibraryCache: {err:?}");
+    }
 
-    let mut mpv_handler = LibMpvHandler::initialize_libmpv(volume).unwrap();
-    let mpv_client = mpv_handler.create_client().unwrap();
+    let (mut mpv_handler, mpv_client) = match LibMpvHandler::initialize_libmpv(
+        config.volume,
+        config.shuffle_mode,
+        config.repeat_mode,
+        audio_cache,
+        ShuffleBag::load(&url),
+        NetworkWorker::spawn(&url, library_cache.clone()),
+    )
+    .and_then(|mpv_handler| {
+        let mpv_client = mpv_handler.create_client()?;
+        Ok((mpv_handler, mpv_client))
+    }) {
+        Ok(mpv) => mpv,
+        Err(err) => {
+            eprintln!("Failed to initialize player, {err}");
+            std::process::exit(-1);
+        }
+    };
 
-    let audiofile = get_random_audiofile(&url).unwrap();
-    log::debug!("Playing: {}", audiofile.path);
-    let audiofile_url = audiofile_to_url(&url, &audiofile);
-    mpv_handler.load_file(&audiofile_url).unwrap();
+    if offline {
+        mpv_handler.enter_offline_mode();
+    }
+    if let Err(err) = mpv_handler
+        .load_next_file(&url, &library_cache)
+        .or_else(|err| {
+            log::error!("LoadNextFile: {err:?}");
+            mpv_handler.recover_load_next(&url, &library_cache)
+        })
+    {
+        log::error!("LoadNextFile: {err:?}");
+    }
 
+    let (events_s, events_r) = crossbeam::channel::unbounded();
     let (tui_s, tui_r) = crossbeam::channel::unbounded();
+    // Without a TUI nothing reads its channel, so events only go to IPC subscribers.
+    let tui_s = (!no_tui).then_some(tui_s);
     let (libmpv_s, libmpv_r) = crossbeam::channel::unbounded();
+
+    let ipc_subscribers = match sonictunes::ipc::IPCServer::bind(&url) {
+        Ok(ipc_server) => {
+            let ipc_subscribers = ipc_server.subscribers();
+            let libmpv_s = libmpv_s.clone();
+            std::thread::spawn(move || {
+                log::debug!("IPCServer: START");
+                ipc_server.handle_connections(libmpv_s);
+            });
+            Some(ipc_subscribers)
+        }
+        Err(err) => {
+            log::error!("IPCServer: {err:?}");
+            None
+        }
+    };
+    let event_subscribers = ipc_subscribers.clone().unwrap_or_default();
     let (mc_tui_s, mc_tui_r) = crossbeam::channel::unbounded();
 
+    let library_cache2 = library_cache.clone();
     let mc_tui_s2 = mc_tui_s.clone();
-    let tui_s2 = tui_s.clone();
+    let events_s2 = events_s.clone();
     let libmpv_s2 = libmpv_s.clone();
 
     let mut mc_os_interface =
-        sonictunes::mc_os_interface::MCOSInterface::new(libmpv_s.clone()).unwrap();
+        match sonictunes::mc_os_interface::MCOSInterface::new(libmpv_s.clone()) {
+            Ok(mc_os_interface) => mc_os_interface,
+            Err(err) => {
+                eprintln!("Failed to initialize media controls, {err}");
+                std::process::exit(-1);
+            }
+        };
 
-    crossbeam::scope(move |scope| {
-        scope.spawn(move |_| {
-            log::debug!("TUI: START");
-            sonictunes::tui::tui(libmpv_s.clone(), tui_r)
-                .map_err(|err| {
+    let errors = crossbeam::scope(move |scope| {
+        let mut handles = vec![];
+        if !no_tui {
+            handles.push(scope.spawn(move |_| {
+                log::debug!("TUI: START");
+                sonictunes::tui::tui(
+                    libmpv_s.clone(),
+                    tui_r,
+                    library_cache2,
+                    keybindings,
+                    themes,
+                    album_art,
+                )
+                .inspect_err(|err| {
                     log::error!("Tui: {:?}", err);
                     let _ = libmpv_s.send(LibMpvMessage::Quit);
                     let _ = mc_tui_s2.send(LibMpvEventMessage::Quit);
-                    err
                 })
-                .unwrap();
-            log::debug!("TUI: END");
-        });
+                .map_err(|err| err.to_string())?;
+                log::debug!("TUI: END");
+                Ok(())
+            }));
+        }
         scope.spawn(move |_| {
+            for msg in events_r.iter() {
+                if let LibMpvEventMessage::Subscribe(event_s) = msg {
+                    event_subscribers.subscribe(event_s);
+                    continue;
+                }
+                event_subscribers.broadcast(&msg);
+                if let Some(ref tui_s) = tui_s {
+                    let _ = tui_s.send(msg);
+                }
+            }
+        });
+        handles.push(scope.spawn(move |_| {
             log::debug!("MPV: START");
             mpv_handler
-                .run(mpv_client, &url, tui_s.clone(), mc_tui_s.clone(), libmpv_r)
-                .map_err(|err| {
+                .run(
+                    mpv_client,
+                    &url,
+                    library_cache,
+                    events_s.clone(),
+                    mc_tui_s.clone(),
+                    libmpv_r,
+                )
+                .inspect_err(|err| {
                     log::error!("MpvHandler: {:?}", err);
-                    let _ = tui_s.send(LibMpvEventMessage::Quit);
+                    let _ = events_s.send(LibMpvEventMessage::Quit);
                     let _ = mc_tui_s.send(LibMpvEventMessage::Quit);
-                    err
                 })
-                .unwrap();
+                .map_err(|err| err.to_string())?;
             log::debug!("MPV: END");
-        });
-        scope.spawn(move |_| {
+            Ok(())
+        }));
+        handles.push(scope.spawn(move |_| {
             log::debug!("MCOSInterface: START");
             mc_os_interface
                 .handle_signals(mc_tui_r)
-                .map_err(|err| {
+                .inspect_err(|err| {
                     log::error!("MCOSInterface: {:?}", err);
-                    let _ = tui_s2.send(LibMpvEventMessage::Quit);
+                    let _ = events_s2.send(LibMpvEventMessage::Quit);
                     let _ = libmpv_s2.send(LibMpvMessage::Quit);
-                    err
                 })
-                .unwrap();
+                .map_err(|err| err.to_string())?;
             log::debug!("MCOSInterface: END");
-        });
+            Ok(())
+        }));
+
+        join_handles(handles)
     })
-    .unwrap();
+    .unwrap_or_else(|_| vec!["a thread panicked".to_string()]);
+    if ipc_subscribers.is_some() {
+        sonictunes::ipc::remove_socket();
+    }
     if let Some(log_send) = log_send {
         log_send.send_quit_signal();
     }
+    exit_on_errors(&errors);
+}
+
+fn join_handles(
+    handles: Vec<crossbeam::thread::ScopedJoinHandle<'_, Result<(), String>>>,
+) -> Vec<String> {
+    handles
+        .into_iter()
+        .filter_map(|handle| match handle.join() {
+            Ok(Ok(())) => None,
+            Ok(Err(err)) => Some(err),
+            Err(_) => Some("a thread panicked".to_string()),
+        })
+        .collect()
+}
+
+fn exit_on_errors(errors: &[String]) {
+    if errors.is_empty() {
+        return;
+    }
+    for err in errors {
+        eprintln!("Error: {err}");
+    }
+    std::process::exit(-1);
+}
+
+fn attach_tui(
+    url: &str,
+    attached_stream: sonictunes::ipc::AttachedStream,
+    config: &Config,
+    keybindings: Keybindings,
+    themes: Themes,
+    album_art: AlbumArtMode,
+) -> Vec<String> {
+    let library_cache = LibraryCache::new(
+        url,
+        config.cache.enabled,
+        config
+            .cache
+            .refresh_interval
+            .map(std::time::Duration::from_mins),
+    );
+    let library_cache2 = library_cache.clone();
+    std::thread::spawn(move || {
+        if let Err(err) = library_cache2.refresh_if_stale() {
+            log::error!("LibraryCache: {err:?}");
+        }
+    });
+
+    let library_cache3 = library_cache.clone();
+    let (tui_s, tui_r) = crossbeam::channel::unbounded();
+    let (libmpv_s, libmpv_r) = crossbeam::channel::unbounded();
+
+    crossbeam::scope(move |scope| {
+        let handles = vec![
+            scope.spawn(move |_| {
+                log::debug!("TUI: START");
+                sonictunes::tui::tui(
+                    libmpv_s.clone(),
+                    tui_r,
+                    library_cache,
+                    keybindings,
+                    themes,
+                    album_art,
+                )
+                .inspect_err(|err| {
+                    log::error!("Tui: {:?}", err);
+                    let _ = libmpv_s.send(LibMpvMessage::Quit);
+                })
+                .map_err(|err| err.to_string())?;
+                log::debug!("TUI: END");
+                Ok(())
+            }),
+            scope.spawn(move |_| {
+                log::debug!("Attach: START");
+                sonictunes::ipc::run_attached(attached_stream, library_cache3, tui_s, libmpv_r)
+                    .inspect_err(|err| log::error!("Attach: {:?}", err))
+                    .map_err(|err| err.to_string())?;
+                log::debug!("Attach: END");
+                Ok(())
+            }),
+        ];
+
+        join_handles(handles)
+    })
+    .unwrap_or_else(|_| vec!["a thread panicked".to_string()])
+}
+
+#[cfg(unix)]
+fn start_daemon() -> ! {
+    use std::os::unix::process::CommandExt;
+
+    if sonictunes::ipc::send_remote_command("status").is_ok() {
+        eprintln!("Another instance is already running");
+        std::process::exit(-1);
+    }
+
+    let args = std::env::args().skip(1).filter(|arg| arg != "--daemon");
+    let child = std::env::current_exe().and_then(|exe| {
+        std::process::Command::new(exe)
+            .arg("--no-tui")
+            .args(args)
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .process_group(0)
+            .spawn()
+    });
+    let mut child = match child {
+        Ok(child) => child,
+        Err(err) => {
+            eprintln!("Failed to start daemon, {err}");
+            std::process::exit(-1);
+        }
+    };
+
+    for _ in 0..100 {
+        if let Ok(Some(status)) = child.try_wait() {
+            eprintln!("Daemon exited, {status}");
+            std::process::exit(-1);
+        }
+        if sonictunes::ipc::send_remote_command("status").is_ok() {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    println!("Daemon started, pid {}", child.id());
+    std::process::exit(0);
+}
+
+#[cfg(not(unix))]
+fn start_daemon() -> ! {
+    eprintln!("--daemon is not supported on this platform, use --no-tui");
+    std::process::exit(-1);
 }
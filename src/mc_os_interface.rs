@@ -1,6 +1,6 @@
 use crate::{
     SonicTunesError,
-    libmpv_handler::{LibMpvEventMessage, LibMpvMessage},
+    libmpv_handler::{LibMpvEventMessage, LibMpvMessage, PlayerState},
 };
 
 #[derive(Debug)]
@@ -13,6 +13,29 @@ pub enum MCOSInterfaceSignals {
     End,
 }
 
+#[derive(Default)]
+struct Metadata {
+    title: String,
+    artist: Option<String>,
+    album: Option<String>,
+    duration: Option<f64>,
+    cover_url: Option<String>,
+}
+
+impl Metadata {
+    fn apply(&self, media_controller: &mut souvlaki::MediaControls) -> Result<(), SonicTunesError> {
+        media_controller.set_metadata(souvlaki::MediaMetadata {
+            title: Some(&self.title),
+            artist: self.artist.as_deref(),
+            album: self.album.as_deref(),
+            cover_url: self.cover_url.as_deref(),
+            duration: self.duration.map(std::time::Duration::from_secs_f64),
+        })?;
+
+        Ok(())
+    }
+}
+
 pub struct MCOSInterface {
     media_controller: souvlaki::MediaControls,
     #[cfg(target_os = "windows")]
@@ -98,105 +121,92 @@ impl MCOSInterface {
         &mut self,
         tui_r: crossbeam::channel::Receiver<crate::libmpv_handler::LibMpvEventMessage>,
     ) -> Result<(), SonicTunesError> {
-        let mut title = String::new();
-        let mut artist: Option<String> = None;
-        let mut album: Option<String> = None;
-        let mut playback_start = std::time::SystemTime::now();
-        let mut playback_start_offset = 0.0;
-        let mut playback_paused = true;
-        let mut playback_ready = false;
-
-        let mut update_playback_timer = std::time::SystemTime::now();
+        let mut metadata = Metadata::default();
+        let mut state: Option<PlayerState> = None;
 
         self.media_controller
             .set_playback(souvlaki::MediaPlayback::Playing { progress: None })?;
 
-        loop {
-            std::thread::sleep(std::time::Duration::from_millis(16));
-            if let Ok(rec) = tui_r.try_recv() {
+        'signals: loop {
+            // The reported position only has to be refreshed periodically while playing.
+            let update_r = if state.is_some_and(|state| state.is_playing()) {
+                crossbeam::channel::after(std::time::Duration::from_millis(500))
+            } else {
+                crossbeam::channel::never()
+            };
+            let mut rec = None;
+            crossbeam::channel::select! {
+                recv(tui_r) -> msg => match msg {
+                    Ok(msg) => rec = Some(msg),
+                    Err(_) => break,
+                },
+                recv(update_r) -> _ => (),
+            }
+
+            for rec in rec.into_iter().chain(tui_r.try_iter()) {
                 log::debug!("LibMpvEventMessage: {rec:?}");
                 match rec {
-                    LibMpvEventMessage::StartFile => {
-                        playback_ready = false;
-                    }
-                    LibMpvEventMessage::PlaybackRestart(paused) => {
-                        playback_start = std::time::SystemTime::now();
-                        playback_ready = true;
-                        playback_paused = paused;
-                    }
-                    LibMpvEventMessage::FileLoaded(data) => {
-                        playback_start = std::time::SystemTime::now();
-                        playback_start_offset = 0.0;
-                        self.media_controller
-                            .set_metadata(souvlaki::MediaMetadata {
-                                title: Some(&data.media_title),
-                                artist: data.artist.as_deref(),
-                                album: data.album.as_deref(),
-                                ..Default::default()
-                            })?;
-                        title = data.media_title;
-                        artist = data.artist;
-                        album = data.album;
-                    }
-                    LibMpvEventMessage::PlaybackPause => {
-                        playback_start_offset += playback_start.elapsed()?.as_secs_f64();
-                        playback_paused = true;
-                    }
-                    LibMpvEventMessage::PlaybackResume => {
-                        playback_start = std::time::SystemTime::now();
-                        playback_paused = false;
-                    }
-                    LibMpvEventMessage::VolumeUpdate(vol) => {
+                    LibMpvEventMessage::StateUpdate(new_state) => {
                         #[cfg(target_os = "linux")]
-                        self.media_controller.set_volume((vol as f64) / 100.0)?;
+                        if state.is_none_or(|state| state.volume != new_state.volume) {
+                            self.media_controller
+                                .set_volume((new_state.volume as f64) / 100.0)?;
+                        }
+                        if metadata.duration != Some(new_state.duration) {
+                            metadata.duration = Some(new_state.duration);
+                            metadata.apply(&mut self.media_controller)?;
+                        }
+                        state = Some(new_state);
                     }
-                    LibMpvEventMessage::PositionUpdate(pos) => {
-                        playback_start = std::time::SystemTime::now();
-                        playback_start_offset = pos;
+                    LibMpvEventMessage::FileLoaded(data) => {
+                        metadata = Metadata {
+                            title: data.media_title,
+                            artist: data.artist,
+                            album: data.album,
+                            duration: None,
+                            cover_url: metadata.cover_url,
+                        };
+                        metadata.apply(&mut self.media_controller)?;
                     }
-                    LibMpvEventMessage::DurationUpdate(dur) => {
-                        self.media_controller
-                            .set_metadata(souvlaki::MediaMetadata {
-                                title: Some(&title),
-                                artist: artist.as_deref(),
-                                album: album.as_deref(),
-                                duration: Some(std::time::Duration::from_secs_f64(dur)),
-                                ..Default::default()
-                            })?;
+                    LibMpvEventMessage::CoverArtUpdate(path) => {
+                        metadata.cover_url = path.map(|path| format!("file://{path}"));
+                        metadata.apply(&mut self.media_controller)?;
                     }
                     LibMpvEventMessage::Quit => {
-                        break;
+                        break 'signals;
                     }
                     LibMpvEventMessage::PlaylistPosUpdate(_) => (),
+                    LibMpvEventMessage::QueueUpdate(_) => (),
+                    LibMpvEventMessage::ShuffleModeUpdate(_) => (),
+                    LibMpvEventMessage::TimerUpdate(_) => (),
+                    LibMpvEventMessage::LyricsUpdate(_) => (),
+                    LibMpvEventMessage::LibraryRefreshed => (),
+                    LibMpvEventMessage::Subscribe(_) => (),
+                    LibMpvEventMessage::ConnectionUpdate(_, _) => (),
+                    LibMpvEventMessage::Error(_) => (),
+                    // souvlaki does not expose the MPRIS LoopStatus property.
+                    LibMpvEventMessage::RepeatModeUpdate(_) => (),
                 }
             }
 
-            if update_playback_timer.elapsed()?.as_secs_f64() > 0.5 {
-                update_playback_timer = std::time::SystemTime::now();
-                let playback_time = {
-                    if !playback_ready {
-                        0.0
-                    } else if playback_paused {
-                        playback_start_offset
-                    } else {
-                        playback_start_offset + playback_start.elapsed()?.as_secs_f64()
-                    }
-                };
-                if playback_paused {
-                    self.media_controller
-                        .set_playback(souvlaki::MediaPlayback::Paused {
-                            progress: Some(souvlaki::MediaPosition(
-                                std::time::Duration::from_secs_f64(playback_time),
-                            )),
-                        })?;
-                } else {
-                    self.media_controller
-                        .set_playback(souvlaki::MediaPlayback::Playing {
-                            progress: Some(souvlaki::MediaPosition(
-                                std::time::Duration::from_secs_f64(playback_time),
-                            )),
-                        })?;
-                }
+            let Some(current) = state else {
+                continue;
+            };
+            let playback_time = current.position();
+            if current.paused {
+                self.media_controller
+                    .set_playback(souvlaki::MediaPlayback::Paused {
+                        progress: Some(souvlaki::MediaPosition(
+                            std::time::Duration::from_secs_f64(playback_time),
+                        )),
+                    })?;
+            } else {
+                self.media_controller
+                    .set_playback(souvlaki::MediaPlayback::Playing {
+                        progress: Some(souvlaki::MediaPosition(
+                            std::time::Duration::from_secs_f64(playback_time),
+                        )),
+                    })?;
             }
         }
 
@@ -0,0 +1,99 @@
+use crate::{
+    AudioFile, SonicTunesError, audiofile_to_url, get_download_client, library_cache::LibraryCache,
+    ping_server,
+};
+
+#[derive(Debug)]
+pub enum PingReason {
+    Reconnect,
+    EndFile(Option<usize>, f64),
+}
+
+#[derive(Debug)]
+pub enum NetworkRequest {
+    Ping(PingReason),
+    // Forced refreshes bypass the refresh interval.
+    RefreshLibrary(bool),
+    FetchLyrics(AudioFile),
+}
+
+#[derive(Debug)]
+pub enum NetworkResponse {
+    Ping(PingReason, Result<(), String>),
+    LibraryRefreshed(bool, Result<(), String>),
+    Lyrics(String, Result<String, String>),
+}
+
+pub struct NetworkWorker {
+    request_s: crossbeam::channel::Sender<NetworkRequest>,
+    response_r: crossbeam::channel::Receiver<NetworkResponse>,
+}
+
+impl NetworkWorker {
+    pub fn spawn(url: &str, library_cache: LibraryCache) -> Self {
+        let (request_s, request_r) = crossbeam::channel::unbounded();
+        let (response_s, response_r) = crossbeam::channel::unbounded();
+        let url = url.to_string();
+
+        std::thread::spawn(move || {
+            log::debug!("NetworkWorker: START");
+            for request in request_r.iter() {
+                log::debug!("NetworkRequest: {request:?}");
+                let response = match request {
+                    NetworkRequest::Ping(reason) => NetworkResponse::Ping(
+                        reason,
+                        ping_server(&url).map_err(|err| err.to_string()),
+                    ),
+                    NetworkRequest::RefreshLibrary(force) => NetworkResponse::LibraryRefreshed(
+                        force,
+                        if force {
+                            library_cache.refresh().map(|_| ())
+                        } else {
+                            library_cache.refresh_if_stale()
+                        }
+                        .map_err(|err| err.to_string()),
+                    ),
+                    NetworkRequest::FetchLyrics(audiofile) => NetworkResponse::Lyrics(
+                        audiofile.id.clone(),
+                        fetch_text(&url, &audiofile).map_err(|err| err.to_string()),
+                    ),
+                };
+                if response_s.send(response).is_err() {
+                    break;
+                }
+            }
+            log::debug!("NetworkWorker: END");
+        });
+
+        NetworkWorker {
+            request_s,
+            response_r,
+        }
+    }
+
+    pub fn ping(&self, reason: PingReason) {
+        let _ = self.request_s.send(NetworkRequest::Ping(reason));
+    }
+
+    pub fn refresh_library(&self, force: bool) {
+        let _ = self.request_s.send(NetworkRequest::RefreshLibrary(force));
+    }
+
+    pub fn fetch_lyrics(&self, audiofile: AudioFile) {
+        let _ = self.request_s.send(NetworkRequest::FetchLyrics(audiofile));
+    }
+
+    pub fn receiver(&self) -> &crossbeam::channel::Receiver<NetworkResponse> {
+        &self.response_r
+    }
+}
+
+fn fetch_text(url: &str, audiofile: &AudioFile) -> Result<String, SonicTunesError> {
+    let text = get_download_client()?
+        .get(audiofile_to_url(url, audiofile))
+        .send()?
+        .error_for_status()?
+        .text()?;
+
+    Ok(text)
+}
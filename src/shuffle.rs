@@ -0,0 +1,139 @@
+use crate::{AudioFile, SonicTunesError, get_cache_dir_path, library_cache::LibraryCache};
+use rand::seq::SliceRandom;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ShuffleMode {
+    Random,
+    Bag,
+    Off,
+}
+
+impl std::str::FromStr for ShuffleMode {
+    type Err = ();
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        match str {
+            "random" => Ok(ShuffleMode::Random),
+            "bag" => Ok(ShuffleMode::Bag),
+            "off" => Ok(ShuffleMode::Off),
+            _ => Err(()),
+        }
+    }
+}
+
+impl ShuffleMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ShuffleMode::Random => "random",
+            ShuffleMode::Bag => "bag",
+            ShuffleMode::Off => "off",
+        }
+    }
+}
+
+// Picks between saves, the bag is also saved on refill and on quit.
+const SAVE_INTERVAL: usize = 16;
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ShuffleBagFile {
+    url: String,
+    remaining: Vec<String>,
+}
+
+pub struct ShuffleBag {
+    url: String,
+    remaining: Vec<String>,
+    // Position of each id in `audiofiles`, rebuilt when the library changes.
+    index: std::collections::HashMap<String, usize>,
+    audiofiles: std::sync::Arc<Vec<AudioFile>>,
+    generation: Option<u64>,
+    unsaved: usize,
+}
+
+impl ShuffleBag {
+    pub fn load(url: &str) -> Self {
+        let remaining = get_cache_dir_path()
+            .and_then(|dir_path| {
+                std::fs::read_to_string(format!("{dir_path}/shuffle_bag.json")).ok()
+            })
+            .and_then(|shuffle_bag| serde_json::from_str::<ShuffleBagFile>(&shuffle_bag).ok())
+            .filter(|shuffle_bag| shuffle_bag.url == url)
+            .map(|shuffle_bag| shuffle_bag.remaining)
+            .unwrap_or_default();
+
+        ShuffleBag {
+            url: url.to_string(),
+            remaining,
+            index: std::collections::HashMap::new(),
+            audiofiles: std::sync::Arc::new(vec![]),
+            generation: None,
+            unsaved: 0,
+        }
+    }
+
+    pub fn save(&mut self) -> Result<(), SonicTunesError> {
+        let Some(dir_path) = get_cache_dir_path() else {
+            return Ok(());
+        };
+        std::fs::create_dir_all(&dir_path)?;
+        let shuffle_bag = serde_json::to_string(&ShuffleBagFile {
+            url: self.url.clone(),
+            remaining: self.remaining.clone(),
+        })?;
+        std::fs::write(format!("{dir_path}/shuffle_bag.json"), shuffle_bag)?;
+        self.unsaved = 0;
+
+        Ok(())
+    }
+
+    fn save_lazily(&mut self) {
+        self.unsaved += 1;
+        if self.unsaved >= SAVE_INTERVAL
+            && let Err(err) = self.save()
+        {
+            log::error!("ShuffleBag: {err:?}");
+        }
+    }
+
+    pub fn put_back(&mut self, audiofile: &AudioFile) {
+        self.remaining.push(audiofile.id.clone());
+        self.save_lazily();
+    }
+
+    pub fn next(&mut self, library_cache: &LibraryCache) -> Option<AudioFile> {
+        let generation = library_cache.generation();
+        if self.generation != Some(generation) {
+            self.audiofiles = library_cache.audiofiles();
+            self.index = self
+                .audiofiles
+                .iter()
+                .enumerate()
+                .map(|(i, audiofile)| (audiofile.id.clone(), i))
+                .collect();
+            self.generation = Some(generation);
+        }
+        if self.audiofiles.is_empty() {
+            return None;
+        }
+
+        loop {
+            match self.remaining.pop() {
+                Some(id) => {
+                    if let Some(&i) = self.index.get(&id) {
+                        self.save_lazily();
+                        return Some(self.audiofiles[i].clone());
+                    }
+                }
+                None => {
+                    log::debug!("ShuffleBag: refill with {} files", self.audiofiles.len());
+                    self.remaining = self.audiofiles.iter().map(|a| a.id.clone()).collect();
+                    self.remaining.shuffle(&mut rand::rng());
+                    if let Err(err) = self.save() {
+                        log::error!("ShuffleBag: {err:?}");
+                    }
+                }
+            }
+        }
+    }
+}
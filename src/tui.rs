@@ -1,22 +1,47 @@
-mod commands;
-mod keybindings;
+pub mod commands;
+pub mod cover_art;
+mod history;
+pub mod keybindings;
+mod library;
+mod lyrics;
+mod player;
+mod search;
+pub mod theme;
 
-use crate::SonicTunesError;
-use crate::libmpv_handler::{LibMpvEventMessage, LibMpvMessage};
+use crate::libmpv_handler::{
+    LibMpvEventMessage, LibMpvMessage, PlaybackTimer, PlayerState, RepeatMode,
+};
 use crate::tui::commands::{
     TuiCommand, TuiState, generate_completion_suggestions, map_str_to_tuicommand,
 };
-use ratatui::crossterm::event::{self, KeyCode, KeyEvent, KeyModifiers};
+use crate::tui::cover_art::{AlbumArtMode, CoverArt};
+use crate::tui::history::HistorySelection;
+use crate::tui::theme::{Theme, Themes};
+use crate::{
+    AudioFile, SonicTunesError,
+    history::{load_history, remove_from_history},
+    library_cache::LibraryCache,
+    shuffle::ShuffleMode,
+};
+use ratatui::crossterm::event::{self, KeyCode, KeyModifiers, MouseButton, MouseEventKind};
 use ratatui::{
-    DefaultTerminal,
-    style::Stylize,
+    DefaultTerminal, Frame,
+    layout::Rect,
+    text::Line,
     widgets::{Block, Borders},
 };
 use std::fmt::Write;
 
+const MOUSE_VOLUME_STEP: i64 = 5;
+const DOUBLE_CLICK_INTERVAL: std::time::Duration = std::time::Duration::from_millis(400);
+
 pub fn tui(
     libmpv_s: crossbeam::channel::Sender<LibMpvMessage>,
     tui_r: crossbeam::channel::Receiver<LibMpvEventMessage>,
+    library_cache: LibraryCache,
+    mut keybindings: keybindings::Keybindings,
+    mut themes: Themes,
+    album_art: AlbumArtMode,
 ) -> Result<(), SonicTunesError> {
     let mut command_mode = false;
     let mut command_text = "".to_string();
@@ -24,431 +49,765 @@ pub fn tui(
     let mut cursor_position: u16 = 0;
     let mut command_suggestions: Option<Vec<&str>> = None;
     let mut command_suggestions_index: Option<usize> = None;
+    let mut search_mode = false;
+    let mut search_text = "".to_string();
 
-    let keybindings = keybindings::Keybindings::new();
+    let min_width = 12;
+    let mut help_text = generate_help_str(min_width);
+    writeln!(help_text, "Keybindings:").unwrap();
+    writeln!(help_text, "{}", keybindings.generate_help_str(min_width)).unwrap();
+    let help_lines = help_text.lines().count();
     let mut tui_state = TuiState::Player;
 
     let mut title = String::new();
     let mut artist: Option<String> = None;
+    let mut album: Option<String> = None;
     let mut terminal = ratatui::init();
+    let _terminal_guard = TerminalGuard;
+    ratatui::crossterm::execute!(std::io::stdout(), event::EnableMouseCapture)?;
+    let mut progress_bar: Option<Rect> = None;
+    let mut last_click: Option<(std::time::Instant, u16)> = None;
+    let mut cover_art: Option<CoverArt> = None;
+    let mut cover_art_generation: u64 = 0;
+    // What the sixel or kitty image currently on screen was drawn for.
+    let mut graphics_key: Option<(u64, ratatui::layout::Size)> = None;
 
-    let mut history: Vec<String> = Vec::new();
-    let mut current: i64 = 0;
+    let mut history = history::History::new(load_history());
     let mut scroll: u16 = 0;
-    let mut scroll_to_center: bool = false;
-
-    let mut playback_start = std::time::SystemTime::now();
-    let mut playback_start_offset = 0.0;
-    let mut playback_paused = true;
-    let mut playback_ready = false;
-    let mut playback_duration = 0;
-    let mut playback_volume = 0;
-
-    let mut pause_after = None;
-    let mut pause_after_timer: Option<std::time::SystemTime> = None;
-    let mut pause_after_duration: Option<std::time::Duration> = None;
-    let mut quit_after = None;
-    let mut quit_after_timer: Option<std::time::SystemTime> = None;
-    let mut quit_after_duration: Option<std::time::Duration> = None;
-
-    loop {
-        let mut timer_text = None;
-        if let Some(pause_after_timer) = pause_after_timer {
-            let elapsed = pause_after_timer.elapsed();
-            let pause_after_duration = pause_after_duration.unwrap();
-            if let Ok(elapsed) = elapsed {
-                let pause_time_left: std::time::Duration =
-                    pause_after_duration.saturating_sub(elapsed);
-                timer_text = Some(format!("P: {}", secs_to_hms(pause_time_left.as_secs())));
-            }
+
+    let mut audiofiles = library_cache.audiofiles();
+    let mut library_generation = library_cache.generation();
+    let mut library = library::Library::new(&audiofiles);
+
+    let mut search = search::Search::new();
+    search.set_audiofiles(&audiofiles);
+
+    let mut lyrics = lyrics::Lyrics::default();
+
+    let mut queue: Vec<AudioFile> = Vec::new();
+    let mut queue_cursor: usize = 0;
+    let mut queue_scroll: u16 = 0;
+
+    let mut player_state: Option<PlayerState> = None;
+    let mut shuffle_mode = ShuffleMode::Off;
+    let mut repeat_mode = RepeatMode::Off;
+
+    let mut timer: Option<PlaybackTimer> = None;
+    let mut reconnect_at: Option<std::time::SystemTime> = None;
+    let mut offline = false;
+
+    let input_r = spawn_input_reader();
+
+    'tui: loop {
+        if library_cache.generation() != library_generation {
+            library_generation = library_cache.generation();
+            audiofiles = library_cache.audiofiles();
+            library.update(&audiofiles);
+            search.set_audiofiles(&audiofiles);
+            search.update(&search_text);
         }
-        if let Some(quit_after_timer) = quit_after_timer {
-            let elapsed = quit_after_timer.elapsed();
-            let quit_after_duration = quit_after_duration.unwrap();
-            if let Ok(elapsed) = elapsed {
-                let quit_time_left: std::time::Duration =
-                    quit_after_duration.saturating_sub(elapsed);
-                timer_text = Some(format!("Q: {}", secs_to_hms(quit_time_left.as_secs())));
+
+        let timer_text = timer.map(|timer| timer.generate_str());
+        let error_text = if !command_error.trim().is_empty() {
+            Some(command_error.clone())
+        } else {
+            reconnect_at.map(|reconnect_at| {
+                let retry_in = reconnect_at
+                    .duration_since(std::time::SystemTime::now())
+                    .unwrap_or_default();
+                if offline {
+                    format!("Offline, reconnecting in {}s", retry_in.as_secs())
+                } else {
+                    format!("Disconnected, retrying in {}s", retry_in.as_secs())
+                }
+            })
+        };
+
+        let command_line = if command_mode {
+            Some(format!(":{command_text}"))
+        } else if search_mode {
+            Some(format!("/{search_text}"))
+        } else {
+            None
+        };
+        let footer = Footer {
+            command: command_line.as_deref(),
+            error: error_text.as_deref(),
+            cursor_position,
+            timer: timer_text.as_deref(),
+        };
+
+        // Graphics are not part of the ratatui buffer, so they are removed and redrawn on any change.
+        let mut redraw_graphics = false;
+        if album_art.is_graphics() {
+            let key = (matches!(tui_state, TuiState::Player) && cover_art.is_some())
+                .then_some((cover_art_generation, terminal.size()?));
+            if key != graphics_key {
+                if graphics_key.is_some() {
+                    clear_graphics(&mut terminal, album_art)?;
+                }
+                graphics_key = key;
+                redraw_graphics = key.is_some();
             }
         }
 
         match tui_state {
             TuiState::Player => {
-                let playback_time = {
-                    if !playback_ready {
-                        0.0
-                    } else if playback_paused {
-                        playback_start_offset
-                    } else {
-                        playback_start_offset + playback_start.elapsed()?.as_secs_f64()
-                    }
+                let player_view = player::PlayerView {
+                    title: &title,
+                    artist: artist.as_deref(),
+                    album: album.as_deref(),
+                    state: player_state,
+                    shuffle_mode,
+                    repeat_mode,
+                    timer,
+                    upcoming: &queue,
+                    theme: themes.current(),
+                    cover_art: cover_art.as_ref(),
+                    album_art,
                 };
-                let mut playback_time = playback_time.floor() as u64;
-                playback_time = playback_time.min(playback_duration);
-                let symbol = {
-                    if !playback_ready || playback_paused {
-                        "|"
-                    } else {
-                        ">"
-                    }
+                let footer = Footer {
+                    timer: None,
+                    ..footer
                 };
-                let mut to_draw = title.clone();
-                if let Some(ref artist) = artist {
-                    to_draw.push_str(" by ");
-                    to_draw.push_str(artist);
+                let mut layout = None;
+                draw_with(&mut terminal, &footer, themes.current(), |f, inner| {
+                    layout = Some(player_view.render(f, inner))
+                })?;
+                progress_bar = layout.as_ref().map(|layout| layout.progress_bar);
+                if redraw_graphics
+                    && let Some(ref cover_art) = cover_art
+                    && let Some(area) = layout.and_then(|layout| layout.cover_art)
+                {
+                    draw_graphics(cover_art, area, album_art)?;
                 }
-                to_draw.push_str(&format!(
-                    "\n{} {} / {} vol: {}",
-                    symbol,
-                    secs_to_hms(playback_time),
-                    secs_to_hms(playback_duration),
-                    playback_volume
-                ));
+            }
+            TuiState::History => {
+                let to_draw = history.generate_str();
+                let height = terminal.size()?.height.saturating_sub(2);
                 draw(
                     &mut terminal,
                     &to_draw,
-                    &mut 0,
-                    if command_mode {
-                        Some(&command_text)
-                    } else {
-                        None
-                    },
-                    if command_error.trim().is_empty() {
-                        None
-                    } else {
-                        Some(&command_error)
-                    },
-                    cursor_position,
-                    timer_text.as_deref(),
-                    false,
+                    history.scroll(height),
+                    &footer,
+                    themes.current(),
                 )?;
             }
-            TuiState::History => {
+            TuiState::Library => {
+                let to_draw = library.generate_str();
+                let height = terminal.size()?.height.saturating_sub(2);
+                draw(
+                    &mut terminal,
+                    &to_draw,
+                    library.scroll(height),
+                    &footer,
+                    themes.current(),
+                )?;
+            }
+            TuiState::Search => {
+                let to_draw = search.generate_str(&audiofiles);
+                let height = terminal.size()?.height.saturating_sub(2);
+                draw(
+                    &mut terminal,
+                    &to_draw,
+                    search.scroll(height),
+                    &footer,
+                    themes.current(),
+                )?;
+            }
+            TuiState::Queue => {
                 let mut to_draw = "".to_string();
-                let current = current as usize;
-                history.iter().enumerate().for_each(|(i, x)| {
-                    if i == current {
-                        to_draw.push_str("* ")
-                    };
-                    to_draw.push_str(&format!("{x}\n"))
+                if queue.is_empty() {
+                    to_draw.push_str("Queue is empty, playing random tracks\n");
+                }
+                queue.iter().enumerate().for_each(|(i, audiofile)| {
+                    to_draw.push_str(if i == queue_cursor { "> " } else { "  " });
+                    to_draw.push_str(&format!("{i}: {}\n", audiofile.path));
                 });
+                let height = terminal.size()?.height.saturating_sub(2);
+                follow_cursor(&mut queue_scroll, queue_cursor as u16, height);
 
                 draw(
                     &mut terminal,
                     &to_draw,
-                    &mut scroll,
-                    if command_mode {
-                        Some(&command_text)
-                    } else {
-                        None
-                    },
-                    if command_error.trim().is_empty() {
-                        None
-                    } else {
-                        Some(&command_error)
-                    },
-                    cursor_position,
-                    timer_text.as_deref(),
-                    scroll_to_center,
+                    &mut queue_scroll,
+                    &footer,
+                    themes.current(),
                 )?;
-                scroll_to_center = false;
+            }
+            TuiState::Lyrics => {
+                let position = player_state.map_or(0.0, |state| state.position());
+                draw_with(&mut terminal, &footer, themes.current(), |f, inner| {
+                    lyrics.render(f, inner, position, themes.current())
+                })?;
             }
             TuiState::Help => {
-                let min_width = 12;
-                let mut to_draw = generate_help_str(min_width);
-                writeln!(to_draw, "Keybindings:").unwrap();
-                writeln!(to_draw, "{}", keybindings.generate_help_str(min_width)).unwrap();
                 draw(
                     &mut terminal,
-                    &to_draw,
+                    &help_text,
                     &mut scroll,
-                    if command_mode {
-                        Some(&command_text)
-                    } else {
-                        None
-                    },
-                    if command_error.trim().is_empty() {
-                        None
-                    } else {
-                        Some(&command_error)
-                    },
-                    cursor_position,
-                    timer_text.as_deref(),
-                    false,
+                    &footer,
+                    themes.current(),
                 )?;
             }
         };
 
-        if event::poll(std::time::Duration::from_millis(16))? {
-            let event = event::read();
-            if let Ok(event) = event {
-                log::debug!("Event: {event:?}");
-                let mut command = None;
-                if let event::Event::Key(key) = event {
-                    command_error = "".to_string();
-                    if command_mode {
-                        if key.code != event::KeyCode::Tab && key.code != event::KeyCode::BackTab {
-                            command_suggestions_index = None;
-                            command_suggestions = None;
-                        }
+        let tick = if player_state.is_some_and(|state| state.is_playing()) || reconnect_at.is_some()
+        {
+            std::time::Duration::from_millis(250)
+        } else {
+            std::time::Duration::from_secs(1)
+        };
+        let mut input_event = None;
+        let mut rec = None;
+        crossbeam::channel::select! {
+            recv(input_r) -> event => match event {
+                Ok(event) => input_event = Some(event),
+                Err(_) => {
+                    libmpv_s.send(LibMpvMessage::Quit)?;
+                    break;
+                }
+            },
+            recv(tui_r) -> msg => match msg {
+                Ok(msg) => rec = Some(msg),
+                Err(_) => break,
+            },
+            recv(crossbeam::channel::after(tick)) -> _ => (),
+        }
 
-                        if key.code.to_string().len() == 1 {
-                            let c = key.code.to_string().chars().next().unwrap();
-                            if c.is_alphanumeric() || c == '-' || c == '+' || c == ':' {
-                                if cursor_position == command_text.len() as u16 {
-                                    command_text.push(c);
-                                } else {
-                                    command_text.insert(cursor_position.into(), c);
-                                }
-                                cursor_position += 1;
-                            }
-                        } else if key.code == event::KeyCode::Backspace {
-                            if !command_text.is_empty() && cursor_position > 0 {
-                                command_text.remove((cursor_position.saturating_sub(1)).into());
-                                if cursor_position > 0 {
-                                    cursor_position -= 1;
-                                }
-                            }
-                        } else if key.code == event::KeyCode::Esc {
-                            command_mode = false;
-                            command_text = "".to_string();
-                            cursor_position = 0;
-                        } else if key.code == event::KeyCode::Enter {
-                            command = map_str_to_tuicommand(&command_text);
-                            if command.is_none() && !command_text.trim().is_empty() {
-                                command_error = "Error: unknown command".to_string();
-                            }
-                            command_mode = false;
-                            command_text = "".to_string();
+        if let Some(event) = input_event {
+            log::debug!("Event: {event:?}");
+            let mut command = None;
+            let mut commands = vec![];
+            if let event::Event::Key(key) = event {
+                command_error = "".to_string();
+                if search_mode {
+                    let mut search_text_changed = false;
+                    match key.code {
+                        KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                            let i = char_to_byte_index(&search_text, cursor_position);
+                            search_text.insert(i, c);
+                            cursor_position += 1;
+                            search_text_changed = true;
+                        }
+                        KeyCode::Backspace if cursor_position > 0 => {
+                            cursor_position -= 1;
+                            let i = char_to_byte_index(&search_text, cursor_position);
+                            search_text.remove(i);
+                            search_text_changed = true;
+                        }
+                        KeyCode::Left => {
+                            cursor_position = cursor_position.saturating_sub(1);
+                        }
+                        KeyCode::Right
+                            if (cursor_position as usize) < search_text.chars().count() =>
+                        {
+                            cursor_position += 1;
+                        }
+                        KeyCode::Up => search.move_cursor(-1),
+                        KeyCode::Down => search.move_cursor(1),
+                        KeyCode::Enter | KeyCode::Esc => {
+                            search_mode = false;
                             cursor_position = 0;
-                        } else if key.code == event::KeyCode::Char(' ') {
+                        }
+                        _ => (),
+                    }
+                    if search_text_changed {
+                        search.update(&search_text);
+                    }
+                } else if command_mode {
+                    if key.code != event::KeyCode::Tab && key.code != event::KeyCode::BackTab {
+                        command_suggestions_index = None;
+                        command_suggestions = None;
+                    }
+
+                    if key.code.to_string().len() == 1 {
+                        let c = key.code.to_string().chars().next().unwrap();
+                        // `_` and `.` appear in user theme names.
+                        if c.is_alphanumeric() || ['-', '+', ':', '_', '.'].contains(&c) {
                             if cursor_position == command_text.len() as u16 {
-                                command_text.push(' ');
-                                cursor_position += 1;
+                                command_text.push(c);
+                            } else {
+                                command_text.insert(cursor_position.into(), c);
                             }
-                        } else if key.code == event::KeyCode::Left {
+                            cursor_position += 1;
+                        }
+                    } else if key.code == event::KeyCode::Backspace {
+                        if !command_text.is_empty() && cursor_position > 0 {
+                            command_text.remove((cursor_position.saturating_sub(1)).into());
                             if cursor_position > 0 {
                                 cursor_position -= 1;
                             }
-                        } else if key.code == event::KeyCode::Right
-                            && cursor_position < command_text.len() as u16
-                        {
+                        }
+                    } else if key.code == event::KeyCode::Esc {
+                        command_mode = false;
+                        command_text = "".to_string();
+                        cursor_position = 0;
+                    } else if key.code == event::KeyCode::Enter {
+                        command = map_str_to_tuicommand(&command_text);
+                        if command.is_none() && !command_text.trim().is_empty() {
+                            command_error = "Error: unknown command".to_string();
+                        }
+                        command_mode = false;
+                        command_text = "".to_string();
+                        cursor_position = 0;
+                    } else if key.code == event::KeyCode::Char(' ') {
+                        if cursor_position == command_text.len() as u16 {
+                            command_text.push(' ');
                             cursor_position += 1;
-                        } else if key.code == event::KeyCode::Tab
-                            || key.code == event::KeyCode::BackTab
-                        {
-                            if command_suggestions.is_none() {
-                                let suggestions = generate_completion_suggestions(&command_text);
-                                if !suggestions.is_empty() {
-                                    command_suggestions = Some(suggestions);
-                                }
-                            }
-                            if let Some(ref suggestions) = command_suggestions {
-                                let i = if key.code == event::KeyCode::Tab {
-                                    command_suggestions_index.map_or(0, |i| {
-                                        if i < suggestions.len() - 1 { i + 1 } else { 0 }
-                                    })
-                                } else {
-                                    command_suggestions_index.map_or(suggestions.len() - 1, |i| {
-                                        if i != 0 { i - 1 } else { suggestions.len() - 1 }
-                                    })
-                                };
-
-                                command_suggestions_index = Some(i);
-                                let suggestion = suggestions.get(i).unwrap().to_owned();
-                                command_text = suggestion.to_string();
-                                cursor_position = command_text.len() as u16;
-                            }
                         }
-                    } else if let Some(key_command) = keybindings.map_keyevent_to_tuicommand(&key) {
-                        command = Some(key_command.clone());
-                    }
-                    if let Some(command) = command {
-                        log::debug!("Command: {command:?}");
-                        match command {
-                            TuiCommand::State(state) => {
-                                tui_state = state.clone();
-                                scroll_to_center = true;
-                            }
-                            TuiCommand::Quit => {
-                                libmpv_s.send(LibMpvMessage::Quit)?;
-                                break;
-                            }
-                            TuiCommand::Volume(vol) => {
-                                libmpv_s.send(LibMpvMessage::UpdateVolume(vol))?;
+                    } else if key.code == event::KeyCode::Left {
+                        if cursor_position > 0 {
+                            cursor_position -= 1;
+                        }
+                    } else if key.code == event::KeyCode::Right
+                        && cursor_position < command_text.len() as u16
+                    {
+                        cursor_position += 1;
+                    } else if key.code == event::KeyCode::Tab || key.code == event::KeyCode::BackTab
+                    {
+                        if command_suggestions.is_none() {
+                            let suggestions = generate_completion_suggestions(&command_text);
+                            if !suggestions.is_empty() {
+                                command_suggestions = Some(suggestions);
                             }
-                            TuiCommand::SetVolume(vol) => {
-                                libmpv_s.send(LibMpvMessage::SetVolume(vol))?;
+                        }
+                        if let Some(ref suggestions) = command_suggestions {
+                            let i = if key.code == event::KeyCode::Tab {
+                                command_suggestions_index.map_or(0, |i| {
+                                    if i < suggestions.len() - 1 { i + 1 } else { 0 }
+                                })
+                            } else {
+                                command_suggestions_index.map_or(suggestions.len() - 1, |i| {
+                                    if i != 0 { i - 1 } else { suggestions.len() - 1 }
+                                })
+                            };
+
+                            command_suggestions_index = Some(i);
+                            let suggestion = suggestions.get(i).unwrap().to_owned();
+                            command_text = suggestion.to_string();
+                            cursor_position = command_text.len() as u16;
+                        }
+                    }
+                } else if let Some(key_command) = keybindings.map_keyevent_to_tuicommand(&key) {
+                    command = Some(key_command.clone());
+                }
+                commands.extend(command);
+            } else if let event::Event::Mouse(mouse) = event {
+                let line = mouse.row.checked_sub(1);
+                match mouse.kind {
+                    MouseEventKind::ScrollUp if tui_state == TuiState::Player => {
+                        commands.push(TuiCommand::Volume(MOUSE_VOLUME_STEP));
+                    }
+                    MouseEventKind::ScrollDown if tui_state == TuiState::Player => {
+                        commands.push(TuiCommand::Volume(-MOUSE_VOLUME_STEP));
+                    }
+                    MouseEventKind::ScrollUp => commands.push(TuiCommand::Scroll(-1)),
+                    MouseEventKind::ScrollDown => commands.push(TuiCommand::Scroll(1)),
+                    MouseEventKind::Down(MouseButton::Left) => {
+                        let double_click = last_click.is_some_and(|(clicked_at, row)| {
+                            row == mouse.row && clicked_at.elapsed() < DOUBLE_CLICK_INTERVAL
+                        });
+                        last_click = Some((std::time::Instant::now(), mouse.row));
+
+                        let offset = line.and_then(|line| match tui_state {
+                            TuiState::Library => library.cursor_offset(line),
+                            TuiState::Search => search.cursor_offset(line),
+                            TuiState::History => history.cursor_offset(line),
+                            TuiState::Queue => {
+                                let i = (queue_scroll + line) as usize;
+                                (i < queue.len()).then(|| i as i16 - queue_cursor as i16)
                             }
-                            TuiCommand::Seek(offset) => {
-                                libmpv_s.send(LibMpvMessage::UpdatePosition(offset))?;
+                            TuiState::Player | TuiState::Lyrics | TuiState::Help => None,
+                        });
+                        if let Some(offset) = offset {
+                            commands.push(TuiCommand::Scroll(offset));
+                            if double_click {
+                                commands.push(TuiCommand::Activate);
                             }
-                            TuiCommand::SetPosition(pos) => {
-                                libmpv_s.send(LibMpvMessage::SetPosition(pos))?;
+                        } else if tui_state == TuiState::Player
+                            && let Some(bar) = progress_bar
+                            && let Some(position) =
+                                player::seek_position(bar, player_state, mouse.column, mouse.row)
+                        {
+                            commands.push(TuiCommand::SetPosition(position));
+                        }
+                    }
+                    _ => (),
+                }
+            }
+            for command in commands {
+                log::debug!("Command: {command:?}");
+                match command {
+                    TuiCommand::State(state) => {
+                        tui_state = state.clone();
+                        if tui_state == TuiState::History {
+                            history.focus_current();
+                        }
+                    }
+                    TuiCommand::Quit => {
+                        libmpv_s.send(LibMpvMessage::Quit)?;
+                        break 'tui;
+                    }
+                    TuiCommand::Volume(vol) => {
+                        libmpv_s.send(LibMpvMessage::UpdateVolume(vol))?;
+                    }
+                    TuiCommand::SetVolume(vol) => {
+                        libmpv_s.send(LibMpvMessage::SetVolume(vol))?;
+                    }
+                    TuiCommand::Seek(offset) => {
+                        libmpv_s.send(LibMpvMessage::UpdatePosition(offset))?;
+                    }
+                    TuiCommand::SetPosition(pos) => {
+                        libmpv_s.send(LibMpvMessage::SetPosition(pos))?;
+                    }
+                    TuiCommand::PlayPause => {
+                        libmpv_s.send(LibMpvMessage::PlayPause)?;
+                    }
+                    TuiCommand::Stop => {
+                        libmpv_s.send(LibMpvMessage::Stop)?;
+                    }
+                    TuiCommand::PlayNext => {
+                        libmpv_s.send(LibMpvMessage::PlayNext)?;
+                    }
+                    TuiCommand::PlayPrevious => {
+                        libmpv_s.send(LibMpvMessage::PlayPrevious)?;
+                    }
+                    TuiCommand::Scroll(x) if tui_state == TuiState::Library => {
+                        library.move_cursor(x);
+                    }
+                    TuiCommand::Scroll(x) if tui_state == TuiState::Search => {
+                        search.move_cursor(x);
+                    }
+                    TuiCommand::Scroll(x) if tui_state == TuiState::Queue => {
+                        if x > 0 {
+                            queue_cursor =
+                                (queue_cursor + x as usize).min(queue.len().saturating_sub(1));
+                        } else {
+                            queue_cursor = queue_cursor.saturating_sub(x.unsigned_abs() as usize);
+                        }
+                    }
+                    TuiCommand::Scroll(x) if tui_state == TuiState::History => {
+                        history.move_cursor(x);
+                    }
+                    TuiCommand::Scroll(x) if tui_state == TuiState::Lyrics => {
+                        lyrics.move_scroll(x);
+                    }
+                    TuiCommand::Scroll(x) => {
+                        if x > 0 && (scroll as usize) < help_lines.saturating_sub(1) {
+                            scroll += 1;
+                        } else if x < 0 && scroll > 0 {
+                            scroll -= 1;
+                        }
+                    }
+                    TuiCommand::Activate => match tui_state {
+                        TuiState::Library => {
+                            if let Some(audiofile) = library.activate() {
+                                libmpv_s.send(LibMpvMessage::Play(vec![audiofile]))?;
                             }
-                            TuiCommand::PlayPause => {
-                                libmpv_s.send(LibMpvMessage::PlayPause)?;
+                        }
+                        TuiState::Search => {
+                            libmpv_s.send(LibMpvMessage::Play(
+                                search.selected_audiofiles(&audiofiles),
+                            ))?;
+                        }
+                        TuiState::History => match history.selected() {
+                            Some(HistorySelection::Past(entry)) => {
+                                libmpv_s.send(LibMpvMessage::Play(vec![entry.to_audiofile()]))?;
                             }
-                            TuiCommand::Stop => {
-                                libmpv_s.send(LibMpvMessage::Stop)?;
+                            Some(HistorySelection::Session(i)) => {
+                                libmpv_s.send(LibMpvMessage::PlayIndex(i))?;
                             }
-                            TuiCommand::PlayNext => {
-                                libmpv_s.send(LibMpvMessage::PlayNext)?;
+                            None => (),
+                        },
+                        TuiState::Queue => {
+                            if let Some(audiofile) = queue.get(queue_cursor) {
+                                libmpv_s.send(LibMpvMessage::Dequeue(queue_cursor))?;
+                                libmpv_s.send(LibMpvMessage::Play(vec![audiofile.clone()]))?;
                             }
-                            TuiCommand::PlayPrevious => {
-                                libmpv_s.send(LibMpvMessage::PlayPrevious)?;
+                        }
+                        _ => (),
+                    },
+                    TuiCommand::PlaySelected => {
+                        let selected = match tui_state {
+                            TuiState::Library => library.selected_audiofiles(),
+                            TuiState::Search => search.selected_audiofiles(&audiofiles),
+                            TuiState::History => history.selected_audiofile().into_iter().collect(),
+                            _ => vec![],
+                        };
+                        if !selected.is_empty() {
+                            libmpv_s.send(LibMpvMessage::Play(selected))?;
+                        }
+                    }
+                    TuiCommand::EnqueueSelected => {
+                        let selected = match tui_state {
+                            TuiState::Library => library.selected_audiofiles(),
+                            TuiState::Search => search.selected_audiofiles(&audiofiles),
+                            TuiState::History => history.selected_audiofile().into_iter().collect(),
+                            _ => vec![],
+                        };
+                        if !selected.is_empty() {
+                            libmpv_s.send(LibMpvMessage::Enqueue(selected))?;
+                        }
+                    }
+                    TuiCommand::EnqueueSelectedNext => {
+                        let selected = match tui_state {
+                            TuiState::Library => library.selected_audiofiles(),
+                            TuiState::Search => search.selected_audiofiles(&audiofiles),
+                            TuiState::History => history.selected_audiofile().into_iter().collect(),
+                            _ => vec![],
+                        };
+                        if !selected.is_empty() {
+                            libmpv_s.send(LibMpvMessage::EnqueueNext(selected))?;
+                        }
+                    }
+                    TuiCommand::Download => {
+                        let selected = match tui_state {
+                            TuiState::Library => library.selected_audiofiles(),
+                            TuiState::Search => search.selected_audiofiles(&audiofiles),
+                            TuiState::History => history.selected_audiofile().into_iter().collect(),
+                            TuiState::Queue => {
+                                queue.get(queue_cursor).cloned().into_iter().collect()
                             }
-                            TuiCommand::Scroll(x) => {
-                                if x > 0 && scroll < (history.len() - 1) as u16 {
-                                    scroll += 1;
-                                } else if x < 0 && scroll > 0 {
-                                    scroll -= 1;
+                            _ => vec![],
+                        };
+                        if !selected.is_empty() {
+                            libmpv_s.send(LibMpvMessage::Download(selected))?;
+                        }
+                    }
+                    TuiCommand::RemoveSelected => match tui_state {
+                        TuiState::Queue => {
+                            libmpv_s.send(LibMpvMessage::Dequeue(queue_cursor))?;
+                        }
+                        TuiState::History => match history.remove_selected() {
+                            Some(HistorySelection::Past(entry)) => {
+                                if let Err(err) = remove_from_history(&entry) {
+                                    log::error!("History: {err:?}");
+                                    command_error = "Error: history update failed".to_string();
                                 }
                             }
-                            TuiCommand::EnterCommandMode(enter) => {
-                                command_mode = enter;
-                            }
-                            TuiCommand::PauseAfter(min) => {
-                                pause_after = Some(crossbeam::channel::after(
-                                    std::time::Duration::from_mins(min),
-                                ));
-                                pause_after_duration = Some(std::time::Duration::from_mins(min));
-                                pause_after_timer = Some(std::time::SystemTime::now());
-                                quit_after = None;
-                                quit_after_duration = None;
-                                quit_after_timer = None;
-                            }
-                            TuiCommand::QuitAfter(min) => {
-                                quit_after = Some(crossbeam::channel::after(
-                                    std::time::Duration::from_mins(min),
-                                ));
-                                quit_after_duration = Some(std::time::Duration::from_mins(min));
-                                quit_after_timer = Some(std::time::SystemTime::now());
-                                pause_after = None;
-                                pause_after_duration = None;
-                                pause_after_timer = None;
+                            Some(HistorySelection::Session(i)) => {
+                                libmpv_s.send(LibMpvMessage::RemoveIndex(i))?;
                             }
+                            None => (),
+                        },
+                        _ => (),
+                    },
+                    TuiCommand::QueueMoveUp => {
+                        if tui_state == TuiState::Queue && queue_cursor > 0 {
+                            libmpv_s.send(LibMpvMessage::QueueMoveUp(queue_cursor))?;
+                            queue_cursor -= 1;
+                        }
+                    }
+                    TuiCommand::QueueMoveDown => {
+                        if tui_state == TuiState::Queue && queue_cursor + 1 < queue.len() {
+                            libmpv_s.send(LibMpvMessage::QueueMoveDown(queue_cursor))?;
+                            queue_cursor += 1;
+                        }
+                    }
+                    TuiCommand::Refresh => {
+                        libmpv_s.send(LibMpvMessage::RefreshLibrary)?;
+                    }
+                    TuiCommand::ShuffleMode(mode) => {
+                        libmpv_s.send(LibMpvMessage::SetShuffleMode(mode))?;
+                    }
+                    TuiCommand::RepeatMode(Some(mode)) => {
+                        libmpv_s.send(LibMpvMessage::SetRepeatMode(mode))?;
+                    }
+                    TuiCommand::RepeatMode(None) => {
+                        libmpv_s.send(LibMpvMessage::CycleRepeatMode)?;
+                    }
+                    TuiCommand::ClearQueue => {
+                        libmpv_s.send(LibMpvMessage::ClearQueue)?;
+                    }
+                    TuiCommand::ParentDir => {
+                        if tui_state == TuiState::Library {
+                            library.parent_dir();
+                        }
+                    }
+                    TuiCommand::EnterCommandMode(enter) => {
+                        command_mode = enter;
+                    }
+                    TuiCommand::EnterSearchMode => {
+                        tui_state = TuiState::Search;
+                        search_mode = true;
+                        cursor_position = search_text.chars().count() as u16;
+                    }
+                    TuiCommand::PauseAfter(min) => {
+                        libmpv_s.send(LibMpvMessage::PauseAfter(min))?;
+                    }
+                    TuiCommand::QuitAfter(min) => {
+                        libmpv_s.send(LibMpvMessage::QuitAfter(min))?;
+                    }
+                    TuiCommand::Theme(name) => {
+                        if !themes.select(&name) {
+                            command_error = format!("Error: unknown theme `{name}`");
                         }
                     }
                 }
             }
         }
-        if let Ok(rec) = tui_r.try_recv() {
+        for rec in rec.into_iter().chain(tui_r.try_iter()) {
             log::debug!("LibMpvEventMessage: {rec:?}");
             match rec {
-                LibMpvEventMessage::StartFile => {
-                    playback_ready = false;
-                }
-                LibMpvEventMessage::PlaybackRestart(paused) => {
-                    playback_start = std::time::SystemTime::now();
-                    playback_ready = true;
-                    playback_paused = paused;
+                LibMpvEventMessage::StateUpdate(state) => {
+                    player_state = Some(state);
                 }
                 LibMpvEventMessage::FileLoaded(data) => {
-                    playback_start = std::time::SystemTime::now();
-                    playback_start_offset = 0.0;
-                    playback_duration = data.duration.floor() as u64;
-                    playback_volume = data.volume;
                     title = data.media_title;
                     artist = data.artist;
+                    album = data.album;
 
                     let mut entry_text = title.clone();
                     if let Some(ref artist) = artist {
                         entry_text.push_str(" by ");
                         entry_text.push_str(artist);
                     }
-                    if history.is_empty() || (current as usize) >= history.len() {
-                        history.push(format!("{}: {}", history.len(), entry_text));
-                    }
+                    history.push(entry_text, data.audiofile);
                 }
-                LibMpvEventMessage::PlaybackPause => {
-                    playback_start_offset += playback_start.elapsed()?.as_secs_f64();
-                    playback_paused = true;
+                LibMpvEventMessage::CoverArtUpdate(path) => {
+                    cover_art = path.and_then(|path| CoverArt::load(&path));
+                    cover_art_generation += 1;
                 }
-                LibMpvEventMessage::PlaybackResume => {
-                    playback_start = std::time::SystemTime::now();
-                    playback_paused = false;
+                LibMpvEventMessage::LyricsUpdate(text) => {
+                    lyrics = text
+                        .as_deref()
+                        .map(lyrics::Lyrics::parse)
+                        .unwrap_or_default();
                 }
-                LibMpvEventMessage::VolumeUpdate(vol) => {
-                    playback_volume = vol;
+                // The cache is shared with the mpv thread, attached TUIs refresh in run_attached.
+                LibMpvEventMessage::LibraryRefreshed => (),
+                // Consumed by the event forwarder in main.rs.
+                LibMpvEventMessage::Subscribe(_) => (),
+                LibMpvEventMessage::PlaylistPosUpdate(pos) => {
+                    history.set_current(pos);
                 }
-                LibMpvEventMessage::PositionUpdate(pos) => {
-                    playback_start = std::time::SystemTime::now();
-                    playback_start_offset = pos;
+                LibMpvEventMessage::QueueUpdate(audiofiles) => {
+                    queue = audiofiles;
+                    queue_cursor = queue_cursor.min(queue.len().saturating_sub(1));
                 }
-                LibMpvEventMessage::DurationUpdate(dur) => {
-                    playback_duration = dur.floor() as u64;
+                LibMpvEventMessage::ShuffleModeUpdate(mode) => {
+                    shuffle_mode = mode;
                 }
-                LibMpvEventMessage::PlaylistPosUpdate(pos) => {
-                    current = pos;
+                LibMpvEventMessage::RepeatModeUpdate(mode) => {
+                    repeat_mode = mode;
+                }
+                LibMpvEventMessage::TimerUpdate(playback_timer) => {
+                    timer = playback_timer;
+                }
+                LibMpvEventMessage::ConnectionUpdate(retry_at, is_offline) => {
+                    reconnect_at = retry_at;
+                    offline = is_offline;
+                }
+                LibMpvEventMessage::Error(err) => {
+                    command_error = format!("Error: {err}");
                 }
                 LibMpvEventMessage::Quit => {
-                    break;
+                    break 'tui;
                 }
             }
         }
-        if pause_after
-            .as_ref()
-            .and_then(|x| x.try_recv().ok())
-            .is_some()
-        {
-            libmpv_s.send(LibMpvMessage::Pause)?;
-        }
-        if quit_after
-            .as_ref()
-            .and_then(|x| x.try_recv().ok())
-            .is_some()
-        {
-            libmpv_s.send(LibMpvMessage::Quit)?;
-            break;
-        }
     }
-    ratatui::restore();
 
     Ok(())
 }
 
-pub fn draw(
+fn spawn_input_reader() -> crossbeam::channel::Receiver<event::Event> {
+    let (input_s, input_r) = crossbeam::channel::unbounded();
+    std::thread::spawn(move || {
+        loop {
+            match event::read() {
+                Ok(event) => {
+                    if input_s.send(event).is_err() {
+                        break;
+                    }
+                }
+                Err(err) => {
+                    log::error!("Input: {err:?}");
+                    break;
+                }
+            }
+        }
+    });
+
+    input_r
+}
+
+struct TerminalGuard;
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = ratatui::crossterm::execute!(std::io::stdout(), event::DisableMouseCapture);
+        ratatui::restore();
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Footer<'a> {
+    command: Option<&'a str>,
+    error: Option<&'a str>,
+    cursor_position: u16,
+    timer: Option<&'a str>,
+}
+
+fn draw(
     terminal: &mut DefaultTerminal,
     text: &str,
     scroll: &mut u16,
-    command: Option<&str>,
-    error: Option<&str>,
-    cursor_position: u16,
-    timer_text: Option<&str>,
-    scroll_to_center: bool,
+    footer: &Footer,
+    theme: &Theme,
+) -> Result<(), std::io::Error> {
+    draw_with(terminal, footer, theme, |f, inner| {
+        // List views mark the cursor with `> ` and the current session entry with `* `.
+        let lines: Vec<Line> = text
+            .lines()
+            .map(|line| {
+                if line.starts_with("> ") {
+                    Line::styled(line, theme.highlight)
+                } else if line.get(2..).is_some_and(|rest| rest.starts_with("* ")) {
+                    Line::styled(line, theme.current)
+                } else {
+                    Line::from(line)
+                }
+            })
+            .collect();
+        let text = ratatui::widgets::Paragraph::new(lines).style(theme.text);
+        let text = text.scroll((*scroll, 0));
+        f.render_widget(text, inner);
+    })
+}
+
+fn draw_with(
+    terminal: &mut DefaultTerminal,
+    footer: &Footer,
+    theme: &Theme,
+    render: impl FnOnce(&mut Frame, Rect),
 ) -> Result<(), std::io::Error> {
+    let Footer {
+        command,
+        error,
+        cursor_position,
+        timer: timer_text,
+    } = *footer;
     terminal.draw(|f| {
         let area = f.area();
         let block = Block::default()
-            .title(env!("CARGO_PKG_NAME"))
-            .borders(Borders::ALL);
+            .title(Line::styled(env!("CARGO_PKG_NAME"), theme.title))
+            .borders(Borders::ALL)
+            .border_style(theme.border);
         let block = block.title_alignment(ratatui::layout::Alignment::Center);
-        if scroll_to_center {
-            if let Some((i, _)) = text.lines().enumerate().find(|(_, l)| l.starts_with('*')) {
-                let i = i as u16;
-                let inner = block.inner(f.area());
-                if i > (inner.height / 2) {
-                    *scroll = i - (inner.height / 2);
-                } else {
-                    *scroll = 0;
-                }
-            }
-        }
-        let text = ratatui::widgets::Paragraph::new(text);
-        let text = text.scroll((*scroll, 0));
         let inner = block.inner(f.area());
         f.render_widget(block, area);
-        f.render_widget(text, inner);
+        render(f, inner);
         if let Some(error) = error {
-            let text = ratatui::widgets::Paragraph::new(error).light_red();
+            let text = ratatui::widgets::Paragraph::new(error).style(theme.error);
             let mut inner = inner;
             inner.y = inner.height;
             inner.height = 1;
             f.render_widget(text, inner);
         }
         if let Some(command) = command {
-            let text = ratatui::widgets::Paragraph::new(":".to_owned() + command);
+            let text = ratatui::widgets::Paragraph::new(command).style(theme.command);
             let mut inner = inner;
             inner.y = inner.height;
             inner.height = 1;
@@ -459,10 +818,12 @@ pub fn draw(
             ));
         }
         if let Some(timer_text) = timer_text {
-            let text = ratatui::widgets::Paragraph::new(timer_text);
+            let text = ratatui::widgets::Paragraph::new(timer_text).style(theme.timer);
             let mut inner = inner;
             inner.y = inner.height;
-            inner.x = inner.width - timer_text.chars().count() as u16;
+            inner.x = inner
+                .width
+                .saturating_sub(timer_text.chars().count() as u16);
             inner.height = 1;
             f.render_widget(text, inner);
         }
@@ -471,12 +832,52 @@ pub fn draw(
     Ok(())
 }
 
-fn secs_to_hms(seconds: u64) -> String {
-    let h = seconds / 3600;
-    let m = (seconds - h * 3600) / 60;
-    let s = seconds - h * 3600 - m * 60;
+fn draw_graphics(
+    cover_art: &CoverArt,
+    area: Rect,
+    album_art: AlbumArtMode,
+) -> Result<(), std::io::Error> {
+    let cell_size = cover_art::cell_size();
+    let image = match album_art {
+        AlbumArtMode::Sixel => cover_art.sixel(area, cell_size),
+        AlbumArtMode::Kitty => cover_art.kitty(area, cell_size),
+        _ => return Ok(()),
+    };
+    ratatui::crossterm::execute!(
+        std::io::stdout(),
+        ratatui::crossterm::cursor::SavePosition,
+        ratatui::crossterm::cursor::MoveTo(area.x, area.y),
+        ratatui::crossterm::style::Print(image),
+        ratatui::crossterm::cursor::RestorePosition
+    )
+}
+
+fn clear_graphics(
+    terminal: &mut DefaultTerminal,
+    album_art: AlbumArtMode,
+) -> Result<(), std::io::Error> {
+    match album_art {
+        AlbumArtMode::Kitty => ratatui::crossterm::execute!(
+            std::io::stdout(),
+            ratatui::crossterm::style::Print(cover_art::KITTY_DELETE_ALL)
+        ),
+        // Sixel pixels stay until the cells below are repainted.
+        _ => terminal.clear(),
+    }
+}
 
-    format!("{h:02}:{m:02}:{s:02}")
+fn follow_cursor(scroll: &mut u16, cursor_line: u16, height: u16) {
+    if cursor_line < *scroll {
+        *scroll = cursor_line;
+    } else if height > 0 && cursor_line >= *scroll + height {
+        *scroll = cursor_line + 1 - height;
+    }
+}
+
+fn char_to_byte_index(text: &str, char_index: u16) -> usize {
+    text.char_indices()
+        .nth(char_index.into())
+        .map_or(text.len(), |(i, _)| i)
 }
 
 pub fn generate_help_str(min_width: usize) -> String {
@@ -527,10 +928,151 @@ pub fn generate_help_str(min_width: usize) -> String {
         "global", "quit-after=<u64>"
     )
     .unwrap();
+    writeln!(help_str, "{:min_width$} {:min_width$}", "global", "refresh").unwrap();
+    writeln!(
+        help_str,
+        "{:min_width$} {:min_width$}",
+        "global", "shuffle-mode <random|bag|off>"
+    )
+    .unwrap();
+    writeln!(
+        help_str,
+        "{:min_width$} {:min_width$}",
+        "global", "repeat [off|one|all|stop]"
+    )
+    .unwrap();
+    writeln!(
+        help_str,
+        "{:min_width$} {:min_width$}",
+        "global", "view <player|history|library|queue|search|lyrics|help>"
+    )
+    .unwrap();
+    writeln!(
+        help_str,
+        "{:min_width$} {:min_width$}",
+        "global", "theme <name>"
+    )
+    .unwrap();
+    writeln!(
+        help_str,
+        "{:min_width$} {:min_width$}",
+        "library", "activate"
+    )
+    .unwrap();
+    writeln!(
+        help_str,
+        "{:min_width$} {:min_width$}",
+        "library", "play-selected"
+    )
+    .unwrap();
+    writeln!(
+        help_str,
+        "{:min_width$} {:min_width$}",
+        "library", "enqueue-selected"
+    )
+    .unwrap();
+    writeln!(
+        help_str,
+        "{:min_width$} {:min_width$}",
+        "library", "enqueue-selected-next"
+    )
+    .unwrap();
+    writeln!(
+        help_str,
+        "{:min_width$} {:min_width$}",
+        "library", "parent-dir"
+    )
+    .unwrap();
+    writeln!(
+        help_str,
+        "{:min_width$} {:min_width$}",
+        "library", "download"
+    )
+    .unwrap();
+    writeln!(
+        help_str,
+        "{:min_width$} {:min_width$}",
+        "search", "activate"
+    )
+    .unwrap();
+    writeln!(
+        help_str,
+        "{:min_width$} {:min_width$}",
+        "search", "play-selected"
+    )
+    .unwrap();
+    writeln!(
+        help_str,
+        "{:min_width$} {:min_width$}",
+        "search", "enqueue-selected"
+    )
+    .unwrap();
+    writeln!(
+        help_str,
+        "{:min_width$} {:min_width$}",
+        "search", "enqueue-selected-next"
+    )
+    .unwrap();
+    writeln!(
+        help_str,
+        "{:min_width$} {:min_width$}",
+        "search", "download"
+    )
+    .unwrap();
+    writeln!(
+        help_str,
+        "{:min_width$} {:min_width$}",
+        "history", "activate"
+    )
+    .unwrap();
+    writeln!(
+        help_str,
+        "{:min_width$} {:min_width$}",
+        "history", "enqueue-selected"
+    )
+    .unwrap();
+    writeln!(
+        help_str,
+        "{:min_width$} {:min_width$}",
+        "history", "enqueue-selected-next"
+    )
+    .unwrap();
+    writeln!(
+        help_str,
+        "{:min_width$} {:min_width$}",
+        "history", "remove-selected"
+    )
+    .unwrap();
+    writeln!(
+        help_str,
+        "{:min_width$} {:min_width$}",
+        "history", "download"
+    )
+    .unwrap();
+    writeln!(help_str, "{:min_width$} {:min_width$}", "queue", "activate").unwrap();
+    writeln!(
+        help_str,
+        "{:min_width$} {:min_width$}",
+        "queue", "remove-selected"
+    )
+    .unwrap();
+    writeln!(
+        help_str,
+        "{:min_width$} {:min_width$}",
+        "queue", "queue-move-up"
+    )
+    .unwrap();
+    writeln!(
+        help_str,
+        "{:min_width$} {:min_width$}",
+        "queue", "queue-move-down"
+    )
+    .unwrap();
+    writeln!(help_str, "{:min_width$} {:min_width$}", "queue", "download").unwrap();
     writeln!(
         help_str,
         "{:min_width$} {:min_width$}",
-        "global", "view <player|history|help>"
+        "global", "queue-clear"
     )
     .unwrap();
 
@@ -1,3 +1,6 @@
+use crate::libmpv_handler::RepeatMode;
+use crate::shuffle::ShuffleMode;
+
 #[derive(Debug, Clone)]
 pub enum TuiCommand {
     State(TuiState),
@@ -11,15 +14,34 @@ pub enum TuiCommand {
     PlayPrevious,
     Scroll(i16),
     EnterCommandMode(bool),
+    EnterSearchMode,
     PauseAfter(u64),
     QuitAfter(u64),
     Stop,
+    Activate,
+    PlaySelected,
+    EnqueueSelected,
+    EnqueueSelectedNext,
+    ParentDir,
+    RemoveSelected,
+    QueueMoveUp,
+    QueueMoveDown,
+    ClearQueue,
+    Refresh,
+    ShuffleMode(ShuffleMode),
+    RepeatMode(Option<RepeatMode>),
+    Download,
+    Theme(String),
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum TuiState {
     Player,
     History,
+    Library,
+    Queue,
+    Search,
+    Lyrics,
     Help,
 }
 
@@ -93,11 +115,75 @@ fn view(args: &mut std::str::SplitWhitespace<'_>) -> Option<TuiCommand> {
     match arg {
         "player" => Some(TuiCommand::State(TuiState::Player)),
         "history" => Some(TuiCommand::State(TuiState::History)),
+        "library" => Some(TuiCommand::State(TuiState::Library)),
+        "queue" => Some(TuiCommand::State(TuiState::Queue)),
+        "search" => Some(TuiCommand::State(TuiState::Search)),
+        "lyrics" => Some(TuiCommand::State(TuiState::Lyrics)),
         "help" => Some(TuiCommand::State(TuiState::Help)),
         _ => None,
     }
 }
 
+fn activate(_: &mut std::str::SplitWhitespace<'_>) -> Option<TuiCommand> {
+    Some(TuiCommand::Activate)
+}
+
+fn playselected(_: &mut std::str::SplitWhitespace<'_>) -> Option<TuiCommand> {
+    Some(TuiCommand::PlaySelected)
+}
+
+fn enqueueselected(_: &mut std::str::SplitWhitespace<'_>) -> Option<TuiCommand> {
+    Some(TuiCommand::EnqueueSelected)
+}
+
+fn enqueueselectednext(_: &mut std::str::SplitWhitespace<'_>) -> Option<TuiCommand> {
+    Some(TuiCommand::EnqueueSelectedNext)
+}
+
+fn parentdir(_: &mut std::str::SplitWhitespace<'_>) -> Option<TuiCommand> {
+    Some(TuiCommand::ParentDir)
+}
+
+fn removeselected(_: &mut std::str::SplitWhitespace<'_>) -> Option<TuiCommand> {
+    Some(TuiCommand::RemoveSelected)
+}
+
+fn queuemoveup(_: &mut std::str::SplitWhitespace<'_>) -> Option<TuiCommand> {
+    Some(TuiCommand::QueueMoveUp)
+}
+
+fn queuemovedown(_: &mut std::str::SplitWhitespace<'_>) -> Option<TuiCommand> {
+    Some(TuiCommand::QueueMoveDown)
+}
+
+fn clearqueue(_: &mut std::str::SplitWhitespace<'_>) -> Option<TuiCommand> {
+    Some(TuiCommand::ClearQueue)
+}
+
+fn refresh(_: &mut std::str::SplitWhitespace<'_>) -> Option<TuiCommand> {
+    Some(TuiCommand::Refresh)
+}
+
+fn download(_: &mut std::str::SplitWhitespace<'_>) -> Option<TuiCommand> {
+    Some(TuiCommand::Download)
+}
+
+fn theme(args: &mut std::str::SplitWhitespace<'_>) -> Option<TuiCommand> {
+    Some(TuiCommand::Theme(args.next()?.to_string()))
+}
+
+fn shufflemode(args: &mut std::str::SplitWhitespace<'_>) -> Option<TuiCommand> {
+    let shuffle_mode: ShuffleMode = args.next()?.parse().ok()?;
+    Some(TuiCommand::ShuffleMode(shuffle_mode))
+}
+
+fn repeat(args: &mut std::str::SplitWhitespace<'_>) -> Option<TuiCommand> {
+    match args.next() {
+        Some(arg) => Some(TuiCommand::RepeatMode(Some(arg.parse().ok()?))),
+        None => Some(TuiCommand::RepeatMode(None)),
+    }
+}
+
 type CmdFn = fn(&mut std::str::SplitWhitespace<'_>) -> Option<TuiCommand>;
 
 static COMMANDS: phf::Map<
@@ -115,6 +201,21 @@ static COMMANDS: phf::Map<
     "pause-after" => pauseafter as CmdFn,
     "quit-after" => quitafter as CmdFn,
     "view" => view as CmdFn,
+    "activate" => activate as CmdFn,
+    "play-selected" => playselected as CmdFn,
+    "enqueue-selected" => enqueueselected as CmdFn,
+    "enqueue-selected-next" => enqueueselectednext as CmdFn,
+    "parent-dir" => parentdir as CmdFn,
+    "remove-selected" => removeselected as CmdFn,
+    "dequeue" => removeselected as CmdFn,
+    "queue-move-up" => queuemoveup as CmdFn,
+    "queue-move-down" => queuemovedown as CmdFn,
+    "queue-clear" => clearqueue as CmdFn,
+    "refresh" => refresh as CmdFn,
+    "download" => download as CmdFn,
+    "shuffle-mode" => shufflemode as CmdFn,
+    "repeat" => repeat as CmdFn,
+    "theme" => theme as CmdFn,
 };
 
 pub fn map_str_to_tuicommand(str: &str) -> Option<TuiCommand> {
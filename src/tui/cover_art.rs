@@ -0,0 +1,246 @@
+use base64::Engine;
+use ratatui::{buffer::Buffer, layout::Rect, style::Color, widgets::Widget};
+
+#[derive(serde::Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum AlbumArtMode {
+    #[default]
+    Auto,
+    Blocks,
+    Sixel,
+    Kitty,
+    Off,
+}
+
+impl AlbumArtMode {
+    // Sixel support can only be detected by querying the terminal, so it has to be chosen explicitly.
+    pub fn resolve(self) -> Self {
+        if self != AlbumArtMode::Auto {
+            return self;
+        }
+        let term = std::env::var("TERM").unwrap_or_default();
+        let term_program = std::env::var("TERM_PROGRAM").unwrap_or_default();
+        if std::env::var_os("KITTY_WINDOW_ID").is_some()
+            || term.contains("kitty")
+            || term.contains("ghostty")
+            || term_program == "WezTerm"
+        {
+            AlbumArtMode::Kitty
+        } else {
+            AlbumArtMode::Blocks
+        }
+    }
+
+    pub fn is_graphics(self) -> bool {
+        matches!(self, AlbumArtMode::Sixel | AlbumArtMode::Kitty)
+    }
+}
+
+pub struct CoverArt {
+    width: usize,
+    height: usize,
+    pixels: Vec<[u8; 3]>,
+}
+
+impl CoverArt {
+    pub fn load(path: &str) -> Option<Self> {
+        let data = std::fs::read(path)
+            .inspect_err(|err| log::error!("CoverArt: {path}: {err:?}"))
+            .ok()?;
+
+        decode_png(&data)
+            .inspect_err(|err| log::error!("CoverArt: {path}: {err:?}"))
+            .ok()
+            .filter(|cover_art| cover_art.width > 0 && cover_art.height > 0)
+    }
+
+    fn fit(&self, max_width: usize, max_height: usize) -> (usize, usize) {
+        let scale = f64::min(
+            max_width as f64 / self.width as f64,
+            max_height as f64 / self.height as f64,
+        );
+        let width = ((self.width as f64 * scale).round() as usize).clamp(1, max_width.max(1));
+        let height = ((self.height as f64 * scale).round() as usize).clamp(1, max_height.max(1));
+
+        (width, height)
+    }
+
+    fn resize(&self, width: usize, height: usize) -> Vec<[u8; 3]> {
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let y0 = y * self.height / height;
+            let y1 = ((y + 1) * self.height / height).max(y0 + 1);
+            for x in 0..width {
+                let x0 = x * self.width / width;
+                let x1 = ((x + 1) * self.width / width).max(x0 + 1);
+                let mut sum = [0u32; 3];
+                for row in self.pixels[y0 * self.width..y1 * self.width].chunks_exact(self.width) {
+                    for pixel in &row[x0..x1] {
+                        for (sum, value) in sum.iter_mut().zip(pixel) {
+                            *sum += u32::from(*value);
+                        }
+                    }
+                }
+                let count = ((x1 - x0) * (y1 - y0)) as u32;
+                pixels.push(sum.map(|sum| (sum / count) as u8));
+            }
+        }
+
+        pixels
+    }
+
+    // Number of cells covered when drawn with a graphics protocol into `area`.
+    fn graphics_size(&self, area: Rect, cell_size: (u16, u16)) -> (usize, usize, u16, u16) {
+        let (cell_width, cell_height) = (usize::from(cell_size.0), usize::from(cell_size.1));
+        let (width, height) = self.fit(
+            usize::from(area.width) * cell_width,
+            usize::from(area.height) * cell_height,
+        );
+        let columns = width.div_ceil(cell_width) as u16;
+        let rows = height.div_ceil(cell_height) as u16;
+
+        (width, height, columns, rows)
+    }
+
+    pub fn sixel(&self, area: Rect, cell_size: (u16, u16)) -> String {
+        let (width, height, _, _) = self.graphics_size(area, cell_size);
+        let pixels = self.resize(width, height);
+        // Colors are quantized to a 6x6x6 cube.
+        let quantize = |value: u8| (u16::from(value) * 5 + 127) / 255;
+        let indices: Vec<u16> = pixels
+            .iter()
+            .map(|[r, g, b]| quantize(*r) * 36 + quantize(*g) * 6 + quantize(*b))
+            .collect();
+
+        let mut sixel = format!("\x1bPq\"1;1;{width};{height}");
+        for index in 0..216 {
+            let percent = |level: u16| level * 100 / 5;
+            sixel.push_str(&format!(
+                "#{index};2;{};{};{}",
+                percent(index / 36),
+                percent(index / 6 % 6),
+                percent(index % 6)
+            ));
+        }
+        for band in indices.chunks(width * 6) {
+            let mut colors: Vec<u16> = band.to_vec();
+            colors.sort_unstable();
+            colors.dedup();
+            for color in colors {
+                sixel.push_str(&format!("#{color}"));
+                let mut run: Option<(u8, usize)> = None;
+                for x in 0..width {
+                    let mut bits = 0u8;
+                    for (row, line) in band.chunks(width).enumerate() {
+                        if line[x] == color {
+                            bits |= 1 << row;
+                        }
+                    }
+                    run = match run {
+                        Some((run_bits, count)) if run_bits == bits => Some((bits, count + 1)),
+                        Some((run_bits, count)) => {
+                            push_sixel_run(&mut sixel, run_bits, count);
+                            Some((bits, 1))
+                        }
+                        None => Some((bits, 1)),
+                    };
+                }
+                if let Some((bits, count)) = run {
+                    push_sixel_run(&mut sixel, bits, count);
+                }
+                sixel.push('$');
+            }
+            sixel.push('-');
+        }
+        sixel.push_str("\x1b\\");
+
+        sixel
+    }
+
+    pub fn kitty(&self, area: Rect, cell_size: (u16, u16)) -> String {
+        let (width, height, columns, rows) = self.graphics_size(area, cell_size);
+        let data: Vec<u8> = self.resize(width, height).into_iter().flatten().collect();
+        let encoded = base64::engine::general_purpose::STANDARD.encode(data);
+
+        let mut kitty = String::new();
+        let chunks: Vec<&[u8]> = encoded.as_bytes().chunks(4096).collect();
+        for (i, chunk) in chunks.iter().enumerate() {
+            let more = u8::from(i + 1 < chunks.len());
+            let chunk = String::from_utf8_lossy(chunk);
+            if i == 0 {
+                kitty.push_str(&format!(
+                    "\x1b_Ga=T,f=24,s={width},v={height},c={columns},r={rows},C=1,q=2,m={more};{chunk}\x1b\\"
+                ));
+            } else {
+                kitty.push_str(&format!("\x1b_Gm={more};{chunk}\x1b\\"));
+            }
+        }
+
+        kitty
+    }
+}
+
+pub const KITTY_DELETE_ALL: &str = "\x1b_Ga=d,d=A,q=2\x1b\\";
+
+// Pixel size of a terminal cell, not every terminal reports it.
+pub fn cell_size() -> (u16, u16) {
+    match ratatui::crossterm::terminal::window_size() {
+        Ok(size) if size.width > 0 && size.height > 0 && size.columns > 0 && size.rows > 0 => {
+            (size.width / size.columns, size.height / size.rows)
+        }
+        _ => (8, 16),
+    }
+}
+
+fn push_sixel_run(sixel: &mut String, bits: u8, count: usize) {
+    let c = char::from(63 + bits);
+    if count > 3 {
+        sixel.push_str(&format!("!{count}{c}"));
+    } else {
+        (0..count).for_each(|_| sixel.push(c));
+    }
+}
+
+impl Widget for &CoverArt {
+    // Each cell shows two pixels, the upper half block in the foreground and the lower in the background.
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let (width, height) = self.fit(usize::from(area.width), usize::from(area.height) * 2);
+        let pixels = self.resize(width, height);
+        for y in 0..height.div_ceil(2) {
+            for x in 0..width {
+                let [r, g, b] = pixels[2 * y * width + x];
+                let bg = pixels
+                    .get((2 * y + 1) * width + x)
+                    .filter(|_| 2 * y + 1 < height)
+                    .map_or(Color::Reset, |[r, g, b]| Color::Rgb(*r, *g, *b));
+                buf[(area.x + x as u16, area.y + y as u16)]
+                    .set_symbol("▀")
+                    .set_fg(Color::Rgb(r, g, b))
+                    .set_bg(bg);
+            }
+        }
+    }
+}
+
+fn decode_png(data: &[u8]) -> Result<CoverArt, png::DecodingError> {
+    let mut decoder = png::Decoder::new(std::io::Cursor::new(data));
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+    let mut buf = vec![0; reader.output_buffer_size().unwrap_or_default()];
+    let info = reader.next_frame(&mut buf)?;
+    let buf = &buf[..info.buffer_size()];
+
+    let pixels = match info.color_type {
+        png::ColorType::Rgb => buf.chunks_exact(3).map(|p| [p[0], p[1], p[2]]).collect(),
+        png::ColorType::Rgba => buf.chunks_exact(4).map(|p| [p[0], p[1], p[2]]).collect(),
+        png::ColorType::Grayscale => buf.iter().map(|&v| [v, v, v]).collect(),
+        png::ColorType::GrayscaleAlpha => buf.chunks_exact(2).map(|p| [p[0], p[0], p[0]]).collect(),
+        png::ColorType::Indexed => unreachable!("expanded by normalize_to_color8"),
+    };
+
+    Ok(CoverArt {
+        width: info.width as usize,
+        height: info.height as usize,
+        pixels,
+    })
+}
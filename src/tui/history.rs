@@ -0,0 +1,136 @@
+use crate::AudioFile;
+use crate::history::HistoryEntry;
+use crate::tui::follow_cursor;
+
+pub enum HistorySelection {
+    Past(HistoryEntry),
+    Session(usize),
+}
+
+pub struct History {
+    past: Vec<HistoryEntry>,
+    session: Vec<(String, Option<AudioFile>)>,
+    current: usize,
+    cursor: usize,
+    scroll: u16,
+    center: bool,
+}
+
+impl History {
+    pub fn new(past: Vec<HistoryEntry>) -> Self {
+        History {
+            past,
+            session: vec![],
+            current: 0,
+            cursor: 0,
+            scroll: 0,
+            center: false,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.past.len() + self.session.len()
+    }
+
+    pub fn push(&mut self, entry_text: String, audiofile: Option<AudioFile>) {
+        if self.current >= self.session.len() {
+            self.session.push((entry_text, audiofile));
+        }
+    }
+
+    pub fn set_current(&mut self, pos: i64) {
+        self.current = pos as usize;
+    }
+
+    pub fn focus_current(&mut self) {
+        self.cursor = (self.past.len() + self.current).min(self.len().saturating_sub(1));
+        self.center = true;
+    }
+
+    pub fn move_cursor(&mut self, offset: i16) {
+        if offset > 0 {
+            self.cursor = (self.cursor + offset as usize).min(self.len().saturating_sub(1));
+        } else {
+            self.cursor = self.cursor.saturating_sub(offset.unsigned_abs() as usize);
+        }
+    }
+
+    pub fn selected(&self) -> Option<HistorySelection> {
+        if self.cursor < self.past.len() {
+            Some(HistorySelection::Past(self.past[self.cursor].clone()))
+        } else if self.cursor - self.past.len() < self.session.len() {
+            Some(HistorySelection::Session(self.cursor - self.past.len()))
+        } else {
+            None
+        }
+    }
+
+    pub fn remove_selected(&mut self) -> Option<HistorySelection> {
+        let selected = self.selected()?;
+        match selected {
+            HistorySelection::Past(_) => {
+                self.past.remove(self.cursor);
+            }
+            HistorySelection::Session(i) => {
+                self.session.remove(i);
+                if i < self.current {
+                    self.current -= 1;
+                }
+            }
+        }
+        self.cursor = self.cursor.min(self.len().saturating_sub(1));
+
+        Some(selected)
+    }
+
+    pub fn selected_audiofile(&self) -> Option<AudioFile> {
+        if self.cursor < self.past.len() {
+            Some(self.past[self.cursor].to_audiofile())
+        } else {
+            self.session
+                .get(self.cursor - self.past.len())
+                .and_then(|(_, audiofile)| audiofile.clone())
+        }
+    }
+
+    pub fn generate_str(&self) -> String {
+        let mut history_str = String::new();
+        for (i, entry) in self.past.iter().enumerate() {
+            history_str.push_str(if i == self.cursor { "> " } else { "  " });
+            history_str.push_str(&entry.generate_str());
+            history_str.push('\n');
+        }
+        for (i, (entry_text, _)) in self.session.iter().enumerate() {
+            history_str.push_str(if self.past.len() + i == self.cursor {
+                "> "
+            } else {
+                "  "
+            });
+            if i == self.current {
+                history_str.push_str("* ");
+            }
+            history_str.push_str(&format!("{i}: {entry_text}"));
+            history_str.push('\n');
+        }
+
+        history_str
+    }
+
+    pub fn cursor_offset(&self, line: u16) -> Option<i16> {
+        let i = usize::from(self.scroll + line);
+
+        (i < self.len()).then(|| i as i16 - self.cursor as i16)
+    }
+
+    pub fn scroll(&mut self, height: u16) -> &mut u16 {
+        let cursor_line = self.cursor as u16;
+        if self.center {
+            self.scroll = cursor_line.saturating_sub(height / 2);
+            self.center = false;
+        } else {
+            follow_cursor(&mut self.scroll, cursor_line, height);
+        }
+
+        &mut self.scroll
+    }
+}
@@ -1,148 +1,251 @@
-use crate::tui::commands::{TuiCommand, TuiState};
-use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use crate::SonicTunesError;
+use crate::tui::commands::{TuiCommand, map_str_to_tuicommand};
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+
+const DEFAULT_KEYBINDINGS: [(&str, &str); 30] = [
+    ("1", "view player"),
+    ("2", "view history"),
+    ("3", "view library"),
+    ("4", "view queue"),
+    ("5", "view lyrics"),
+    ("0", "view help"),
+    ("q", "quit"),
+    ("{", "vol -1"),
+    ("}", "vol +1"),
+    ("[", "vol -10"),
+    ("]", "vol +10"),
+    ("left", "seek -10"),
+    ("shift+left", "seek -60"),
+    ("right", "seek +10"),
+    ("shift+right", "seek +60"),
+    ("space", "play-pause"),
+    ("z", "play-prev"),
+    ("b", "play-next"),
+    ("r", "repeat"),
+    ("j", "scroll +1"),
+    ("k", "scroll -1"),
+    ("enter", "activate"),
+    ("p", "play-selected"),
+    ("e", "enqueue-selected"),
+    ("E", "enqueue-selected-next"),
+    ("d", "remove-selected"),
+    ("K", "queue-move-up"),
+    ("J", "queue-move-down"),
+    ("D", "download"),
+    ("backspace", "parent-dir"),
+];
 
 pub struct Keybindings {
-    pub map: std::collections::HashMap<KeyEvent, (TuiCommand, Option<&'static str>)>,
+    map: std::collections::HashMap<Vec<KeyEvent>, (TuiCommand, Option<String>)>,
+    pending: Vec<KeyEvent>,
 }
 
 impl Keybindings {
-    pub fn new() -> Self {
-        let keybindings = std::collections::HashMap::from([
-            (
-                KeyEvent::new(KeyCode::Char('1'), KeyModifiers::NONE),
-                (TuiCommand::State(TuiState::Player), Some("view player")),
-            ),
-            (
-                KeyEvent::new(KeyCode::Char('2'), KeyModifiers::NONE),
-                (TuiCommand::State(TuiState::History), Some("view history")),
-            ),
-            (
-                KeyEvent::new(KeyCode::Char('0'), KeyModifiers::NONE),
-                (TuiCommand::State(TuiState::Help), Some("view help")),
-            ),
-            (
-                KeyEvent::new(KeyCode::Char('q'), KeyModifiers::NONE),
-                (TuiCommand::Quit, Some("quit, q")),
-            ),
-            (
-                KeyEvent::new(KeyCode::Char('{'), KeyModifiers::NONE),
-                (TuiCommand::Volume(-1), Some("vol -1")),
-            ),
-            (
-                KeyEvent::new(KeyCode::Char('}'), KeyModifiers::NONE),
-                (TuiCommand::Volume(1), Some("vol +1")),
-            ),
-            (
-                KeyEvent::new(KeyCode::Char('['), KeyModifiers::NONE),
-                (TuiCommand::Volume(-10), Some("vol -10")),
-            ),
-            (
-                KeyEvent::new(KeyCode::Char(']'), KeyModifiers::NONE),
-                (TuiCommand::Volume(10), Some("vol +10")),
-            ),
-            (
-                KeyEvent::new(KeyCode::Left, KeyModifiers::NONE),
-                (TuiCommand::Seek(-10.0), Some("seek -10")),
-            ),
-            (
-                KeyEvent::new(KeyCode::Left, KeyModifiers::SHIFT),
-                (TuiCommand::Seek(-60.0), Some("seek -60")),
-            ),
-            (
-                KeyEvent::new(KeyCode::Right, KeyModifiers::NONE),
-                (TuiCommand::Seek(10.0), Some("seek +10")),
-            ),
-            (
-                KeyEvent::new(KeyCode::Right, KeyModifiers::SHIFT),
-                (TuiCommand::Seek(60.0), Some("seek -60")),
-            ),
-            (
-                KeyEvent::new(KeyCode::Char(' '), KeyModifiers::NONE),
-                (TuiCommand::PlayPause, Some("play-pause")),
-            ),
-            (
-                KeyEvent::new(KeyCode::Char('z'), KeyModifiers::NONE),
-                (TuiCommand::PlayPrevious, Some("play-prev")),
-            ),
-            (
-                KeyEvent::new(KeyCode::Char('b'), KeyModifiers::NONE),
-                (TuiCommand::PlayNext, Some("play-next")),
-            ),
-            (
-                KeyEvent::new(KeyCode::Char('j'), KeyModifiers::NONE),
-                (TuiCommand::Scroll(1), Some("scroll +1")),
-            ),
+    pub fn new(
+        user_keybindings: &std::collections::BTreeMap<String, String>,
+    ) -> Result<Self, SonicTunesError> {
+        let mut keybindings = std::collections::HashMap::from([
             (
-                KeyEvent::new(KeyCode::Char('k'), KeyModifiers::NONE),
-                (TuiCommand::Scroll(-1), Some("scroll -1")),
+                vec![KeyEvent::new(KeyCode::Char(':'), KeyModifiers::NONE)],
+                (TuiCommand::EnterCommandMode(true), None),
             ),
             (
-                KeyEvent::new(KeyCode::Char(':'), KeyModifiers::NONE),
-                (TuiCommand::EnterCommandMode(true), None),
+                vec![KeyEvent::new(KeyCode::Char('/'), KeyModifiers::NONE)],
+                (TuiCommand::EnterSearchMode, None),
             ),
             (
-                KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE),
+                vec![KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE)],
                 (TuiCommand::EnterCommandMode(false), None),
             ),
         ]);
+        for (key_spec, command_str) in DEFAULT_KEYBINDINGS {
+            let (keys, command) = parse_keybinding(key_spec, command_str)?;
+            keybindings.insert(keys, (command, Some(command_str.to_string())));
+        }
+
+        let mut user_map: std::collections::HashMap<Vec<KeyEvent>, (TuiCommand, Option<String>)> =
+            std::collections::HashMap::new();
+        let mut unbound = vec![];
+        for (key_spec, command_str) in user_keybindings {
+            let command_str = command_str.trim();
+            let keys = parse_key_sequence(key_spec).ok_or(SonicTunesError::ConfigError(
+                format!("keybindings: invalid key `{key_spec}`"),
+            ))?;
+            if let Some(other) = user_map
+                .keys()
+                .chain(unbound.iter())
+                .find(|other| other.starts_with(&keys) || keys.starts_with(other))
+            {
+                return Err(SonicTunesError::ConfigError(format!(
+                    "keybindings: `{key_spec}` conflicts with `{}`",
+                    key_sequence_to_string(other)
+                )));
+            }
+            if command_str.is_empty() {
+                unbound.push(keys);
+                continue;
+            }
+            let (keys, command) = parse_keybinding(key_spec, command_str)?;
+            user_map.insert(keys, (command, Some(command_str.to_string())));
+        }
+
+        keybindings.retain(|keys, _| {
+            !user_map
+                .keys()
+                .chain(unbound.iter())
+                .any(|other| other.starts_with(keys) || keys.starts_with(other))
+        });
+        keybindings.extend(user_map);
 
-        return Keybindings { map: keybindings };
+        Ok(Keybindings {
+            map: keybindings,
+            pending: vec![],
+        })
     }
 
-    pub fn map_keyevent_to_tuicommand(&self, event: &KeyEvent) -> Option<TuiCommand> {
-        self.map.get(event).map(|(command, _)| command.clone())
+    pub fn map_keyevent_to_tuicommand(&mut self, event: &KeyEvent) -> Option<TuiCommand> {
+        if event.kind != KeyEventKind::Press {
+            return None;
+        }
+        self.pending.push(normalize_key_event(*event));
+        if let Some((command, _)) = self.map.get(&self.pending) {
+            self.pending.clear();
+            return Some(command.clone());
+        }
+        if self.map.keys().any(|keys| keys.starts_with(&self.pending)) {
+            return None;
+        }
+
+        let retry = self.pending.len() > 1;
+        self.pending.clear();
+        if retry {
+            self.map_keyevent_to_tuicommand(event)
+        } else {
+            None
+        }
     }
 
     pub fn generate_help_str(&self, min_width: usize) -> String {
-        let keybindings = self
+        let mut keybindings_help_str: Vec<(String, String)> = self
             .map
             .iter()
-            .filter_map(|(k, &(_, help_str))| help_str.map(|h| (k, h)));
+            .filter_map(|(keys, (_, help_str))| {
+                help_str
+                    .as_ref()
+                    .map(|h| (key_sequence_to_string(keys), h.clone()))
+            })
+            .collect();
+        keybindings_help_str
+            .sort_unstable_by(|(a_keys, a), (b_keys, b)| a.cmp(b).then_with(|| a_keys.cmp(b_keys)));
 
-        let mut keybindings_help_str = vec![];
-        for (key_event, description) in keybindings {
-            let mut help_str = String::new();
-            help_str += &match key_event.code {
-                KeyCode::Char(' ') => format!(
-                    "{:min_width$}  {:min_width$}  {description}",
-                    "global", "space"
-                ),
-                KeyCode::Char(c) => {
-                    format!(
-                        "{:min_width$}  {:min_width$}  {description}",
-                        "global",
-                        if key_event.modifiers == KeyModifiers::NONE {
-                            c.to_string()
-                        } else {
-                            format!("{c}+{}", key_event.modifiers.to_string())
-                        }
-                    )
-                }
-                key_code => format!(
+        keybindings_help_str
+            .iter()
+            .map(|(keys, description)| {
+                format!(
                     "{:min_width$}  {:min_width$}  {description}",
-                    "global",
-                    if key_event.modifiers == KeyModifiers::NONE {
-                        key_code.to_string()
-                    } else {
-                        format!("{key_code}+{}", key_event.modifiers.to_string())
-                    }
-                ),
-            };
-            keybindings_help_str.push(help_str);
-        }
+                    "global", keys
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
 
-        keybindings_help_str.sort_unstable_by_key(|str| {
-            str.split_once(' ')
-                .unwrap()
-                .1
-                .trim()
-                .split_once(' ')
-                .unwrap()
-                .1
-                .trim()
-                .to_string()
-        });
+fn parse_keybinding(
+    key_spec: &str,
+    command_str: &str,
+) -> Result<(Vec<KeyEvent>, TuiCommand), SonicTunesError> {
+    let keys = parse_key_sequence(key_spec).ok_or(SonicTunesError::ConfigError(format!(
+        "keybindings: invalid key `{key_spec}`"
+    )))?;
+    let command = map_str_to_tuicommand(command_str).ok_or(SonicTunesError::ConfigError(
+        format!("keybindings: invalid command `{command_str}` for `{key_spec}`"),
+    ))?;
+
+    Ok((keys, command))
+}
+
+fn parse_key_sequence(key_spec: &str) -> Option<Vec<KeyEvent>> {
+    let keys: Option<Vec<KeyEvent>> = key_spec.split_whitespace().map(parse_key).collect();
+
+    keys.filter(|keys| !keys.is_empty())
+}
 
-        keybindings_help_str.join("\n")
+fn parse_key(key_str: &str) -> Option<KeyEvent> {
+    let (modifiers_str, code_str) = if key_str.len() > 1 && key_str.ends_with('+') {
+        (&key_str[..key_str.len() - 1], "+")
+    } else {
+        key_str.rsplit_once('+').unwrap_or(("", key_str))
+    };
+
+    let mut modifiers = KeyModifiers::NONE;
+    for modifier in modifiers_str.split('+').filter(|s| !s.is_empty()) {
+        modifiers |= match modifier.to_lowercase().as_str() {
+            "ctrl" | "control" => KeyModifiers::CONTROL,
+            "alt" => KeyModifiers::ALT,
+            "shift" => KeyModifiers::SHIFT,
+            _ => return None,
+        };
+    }
+
+    let mut chars = code_str.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(c), None) => KeyCode::Char(c),
+        _ => match code_str.to_lowercase().as_str() {
+            "space" => KeyCode::Char(' '),
+            "enter" => KeyCode::Enter,
+            "esc" | "escape" => KeyCode::Esc,
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "backspace" => KeyCode::Backspace,
+            "delete" | "del" => KeyCode::Delete,
+            "insert" | "ins" => KeyCode::Insert,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            s if s.starts_with('f') => KeyCode::F(s[1..].parse().ok()?),
+            _ => return None,
+        },
+    };
+
+    Some(normalize_key_event(KeyEvent::new(code, modifiers)))
+}
+
+fn normalize_key_event(event: KeyEvent) -> KeyEvent {
+    match event.code {
+        KeyCode::Char(c) if event.modifiers.contains(KeyModifiers::SHIFT) => KeyEvent::new(
+            KeyCode::Char(c.to_ascii_uppercase()),
+            event.modifiers - KeyModifiers::SHIFT,
+        ),
+        code => KeyEvent::new(code, event.modifiers),
     }
 }
+
+fn key_sequence_to_string(keys: &[KeyEvent]) -> String {
+    keys.iter()
+        .map(|key_event| {
+            let mut key_str = String::new();
+            if key_event.modifiers.contains(KeyModifiers::CONTROL) {
+                key_str.push_str("ctrl+");
+            }
+            if key_event.modifiers.contains(KeyModifiers::ALT) {
+                key_str.push_str("alt+");
+            }
+            if key_event.modifiers.contains(KeyModifiers::SHIFT) {
+                key_str.push_str("shift+");
+            }
+            match key_event.code {
+                KeyCode::Char(' ') => key_str.push_str("space"),
+                KeyCode::Char(c) => key_str.push(c),
+                code => key_str.push_str(&code.to_string().to_lowercase().replace(' ', "")),
+            }
+            key_str
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
@@ -0,0 +1,200 @@
+use crate::AudioFile;
+use crate::tui::follow_cursor;
+
+#[derive(Default)]
+pub struct LibraryDir {
+    pub dirs: std::collections::BTreeMap<String, LibraryDir>,
+    pub files: Vec<(String, AudioFile)>,
+}
+
+pub enum LibraryEntry<'a> {
+    Parent,
+    Dir(&'a str, &'a LibraryDir),
+    File(&'a str, &'a AudioFile),
+}
+
+impl LibraryDir {
+    pub fn from_audiofiles(audiofiles: &[AudioFile]) -> Self {
+        let mut root = LibraryDir::default();
+        for audiofile in audiofiles {
+            let mut components: Vec<&str> = audiofile
+                .path
+                .split(['/', '\\'])
+                .filter(|c| !c.is_empty())
+                .collect();
+            let Some(file_name) = components.pop() else {
+                continue;
+            };
+
+            let mut dir = &mut root;
+            for component in components {
+                dir = dir.dirs.entry(component.to_string()).or_default();
+            }
+            dir.files.push((file_name.to_string(), audiofile.clone()));
+        }
+        root.sort();
+
+        root
+    }
+
+    fn sort(&mut self) {
+        self.files.sort_by(|(a, _), (b, _)| a.cmp(b));
+        self.dirs.values_mut().for_each(|dir| dir.sort());
+    }
+
+    pub fn collect_audiofiles(&self) -> Vec<AudioFile> {
+        let mut audiofiles = vec![];
+        for dir in self.dirs.values() {
+            audiofiles.append(&mut dir.collect_audiofiles());
+        }
+        audiofiles.extend(self.files.iter().map(|(_, audiofile)| audiofile.clone()));
+
+        audiofiles
+    }
+}
+
+pub struct Library {
+    root: LibraryDir,
+    cwd: Vec<String>,
+    cursor: usize,
+    scroll: u16,
+}
+
+impl Library {
+    pub fn new(audiofiles: &[AudioFile]) -> Self {
+        Library {
+            root: LibraryDir::from_audiofiles(audiofiles),
+            cwd: vec![],
+            cursor: 0,
+            scroll: 0,
+        }
+    }
+
+    // Stays in the current directory while it still exists, otherwise in its closest ancestor.
+    pub fn update(&mut self, audiofiles: &[AudioFile]) {
+        self.root = LibraryDir::from_audiofiles(audiofiles);
+        let mut dir = &self.root;
+        let existing = self
+            .cwd
+            .iter()
+            .take_while(|component| match dir.dirs.get(*component) {
+                Some(child) => {
+                    dir = child;
+                    true
+                }
+                None => false,
+            })
+            .count();
+        if existing < self.cwd.len() {
+            self.cwd.truncate(existing);
+            self.cursor = 0;
+            self.scroll = 0;
+        } else {
+            self.cursor = self.cursor.min(self.entries().len().saturating_sub(1));
+        }
+    }
+
+    fn current_dir(&self) -> &LibraryDir {
+        let mut dir = &self.root;
+        for component in &self.cwd {
+            dir = &dir.dirs[component];
+        }
+
+        dir
+    }
+
+    pub fn entries(&self) -> Vec<LibraryEntry<'_>> {
+        let dir = self.current_dir();
+        let mut entries = vec![];
+        if !self.cwd.is_empty() {
+            entries.push(LibraryEntry::Parent);
+        }
+        entries.extend(
+            dir.dirs
+                .iter()
+                .map(|(name, dir)| LibraryEntry::Dir(name, dir)),
+        );
+        entries.extend(
+            dir.files
+                .iter()
+                .map(|(name, audiofile)| LibraryEntry::File(name, audiofile)),
+        );
+
+        entries
+    }
+
+    pub fn move_cursor(&mut self, offset: i16) {
+        let len = self.entries().len();
+        if offset > 0 {
+            self.cursor = (self.cursor + offset as usize).min(len.saturating_sub(1));
+        } else {
+            self.cursor = self.cursor.saturating_sub(offset.unsigned_abs() as usize);
+        }
+    }
+
+    pub fn activate(&mut self) -> Option<AudioFile> {
+        let entries = self.entries();
+        match entries.get(self.cursor)? {
+            LibraryEntry::Parent => {
+                self.parent_dir();
+                None
+            }
+            LibraryEntry::Dir(name, _) => {
+                let name = name.to_string();
+                self.cwd.push(name);
+                self.cursor = 0;
+                self.scroll = 0;
+                None
+            }
+            LibraryEntry::File(_, audiofile) => Some((*audiofile).clone()),
+        }
+    }
+
+    pub fn parent_dir(&mut self) {
+        if let Some(name) = self.cwd.pop() {
+            self.cursor = self
+                .entries()
+                .iter()
+                .position(|entry| matches!(entry, LibraryEntry::Dir(n, _) if **n == name))
+                .unwrap_or(0);
+        }
+    }
+
+    pub fn selected_audiofiles(&self) -> Vec<AudioFile> {
+        match self.entries().get(self.cursor) {
+            Some(LibraryEntry::Dir(_, dir)) => dir.collect_audiofiles(),
+            Some(LibraryEntry::File(_, audiofile)) => vec![(*audiofile).clone()],
+            Some(LibraryEntry::Parent) | None => vec![],
+        }
+    }
+
+    pub fn generate_str(&self) -> String {
+        let mut library_str = format!("/{}\n", self.cwd.join("/"));
+        for (i, entry) in self.entries().iter().enumerate() {
+            library_str.push_str(if i == self.cursor { "> " } else { "  " });
+            match entry {
+                LibraryEntry::Parent => library_str.push_str("../"),
+                LibraryEntry::Dir(name, _) => {
+                    library_str.push_str(name);
+                    library_str.push('/');
+                }
+                LibraryEntry::File(name, _) => library_str.push_str(name),
+            }
+            library_str.push('\n');
+        }
+
+        library_str
+    }
+
+    pub fn cursor_offset(&self, line: u16) -> Option<i16> {
+        let i = usize::from(self.scroll + line).checked_sub(1)?;
+
+        (i < self.entries().len()).then(|| i as i16 - self.cursor as i16)
+    }
+
+    pub fn scroll(&mut self, height: u16) -> &mut u16 {
+        follow_cursor(&mut self.scroll, self.cursor as u16 + 1, height);
+
+        &mut self.scroll
+    }
+}
@@ -0,0 +1,123 @@
+use crate::tui::theme::Theme;
+use ratatui::{Frame, layout::Rect, style::Stylize, text::Line, widgets::Paragraph};
+
+#[derive(Default)]
+pub struct Lyrics {
+    // Start time of each line, None for unsynced lyrics.
+    lines: Vec<(Option<f64>, String)>,
+    synced: bool,
+    scroll: u16,
+}
+
+impl Lyrics {
+    // Plain text, or LRC with `[mm:ss.xx]` tags where one line may carry several of them.
+    pub fn parse(text: &str) -> Self {
+        let mut offset = 0.0;
+        let mut synced_lines = vec![];
+        let mut plain_lines = vec![];
+        for line in text.lines() {
+            let mut rest = line.trim();
+            let mut times = vec![];
+            let mut is_tag = false;
+            while let Some((tag, after)) = rest.strip_prefix('[').and_then(|r| r.split_once(']')) {
+                if let Some(time) = parse_timestamp(tag) {
+                    times.push(time);
+                } else if let Some((key, value)) = tag.split_once(':')
+                    && key.chars().all(|c| c.is_ascii_alphabetic())
+                {
+                    // Positive offsets show the lines earlier.
+                    if key.eq_ignore_ascii_case("offset")
+                        && let Ok(ms) = value.trim().parse::<f64>()
+                    {
+                        offset = ms / 1000.0;
+                    }
+                    is_tag = true;
+                } else {
+                    break;
+                }
+                rest = after;
+            }
+            let rest = rest.trim().to_string();
+            if !times.is_empty() {
+                synced_lines.extend(times.into_iter().map(|time| (time, rest.clone())));
+            } else if !is_tag {
+                plain_lines.push((None, rest));
+            }
+        }
+
+        if synced_lines.is_empty() {
+            return Lyrics {
+                lines: plain_lines,
+                synced: false,
+                scroll: 0,
+            };
+        }
+        synced_lines.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+
+        Lyrics {
+            lines: synced_lines
+                .into_iter()
+                .map(|(time, line)| (Some(time - offset), line))
+                .collect(),
+            synced: true,
+            scroll: 0,
+        }
+    }
+
+    fn current_line(&self, position: f64) -> Option<usize> {
+        if !self.synced {
+            return None;
+        }
+
+        self.lines
+            .partition_point(|(time, _)| time.is_some_and(|time| time <= position))
+            .checked_sub(1)
+    }
+
+    // Synced lyrics follow the playback position instead.
+    pub fn move_scroll(&mut self, offset: i16) {
+        if self.synced {
+            return;
+        }
+        let max = self.lines.len().saturating_sub(1) as u16;
+        self.scroll = self.scroll.saturating_add_signed(offset).min(max);
+    }
+
+    pub fn render(&self, f: &mut Frame, area: Rect, position: f64, theme: &Theme) {
+        if self.lines.is_empty() {
+            let text = Line::styled("No lyrics for this track", theme.dim).italic();
+            f.render_widget(Paragraph::new(text).centered(), area);
+            return;
+        }
+
+        let current = self.current_line(position);
+        let lines: Vec<Line> = self
+            .lines
+            .iter()
+            .enumerate()
+            .map(|(i, (_, line))| match current {
+                Some(current) if i == current => Line::styled(line.as_str(), theme.current).bold(),
+                Some(current) if i < current => Line::styled(line.as_str(), theme.dim),
+                _ => Line::styled(line.as_str(), theme.text),
+            })
+            .collect();
+        // Keeps the current line in the middle of the view.
+        let scroll = match current {
+            Some(current) => (current as u16).saturating_sub(area.height / 2),
+            None if self.synced => 0,
+            None => self.scroll,
+        };
+
+        f.render_widget(Paragraph::new(lines).centered().scroll((scroll, 0)), area);
+    }
+}
+
+fn parse_timestamp(tag: &str) -> Option<f64> {
+    let (minutes, seconds) = tag.split_once(':')?;
+    if minutes.is_empty() || !minutes.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let seconds: f64 = seconds.replace(':', ".").parse().ok()?;
+
+    Some(minutes.parse::<f64>().ok()? * 60.0 + seconds)
+}
@@ -0,0 +1,229 @@
+use crate::libmpv_handler::{PlaybackTimer, PlayerState, RepeatMode};
+use crate::tui::cover_art::{AlbumArtMode, CoverArt};
+use crate::tui::theme::Theme;
+use crate::{AudioFile, secs_to_hms, shuffle::ShuffleMode};
+use ratatui::{
+    Frame,
+    layout::{Constraint, Layout, Rect},
+    style::Stylize,
+    text::{Line, Span},
+    widgets::{LineGauge, Paragraph},
+};
+
+// Below this width the view drops the album line and splits the status row in two.
+const NARROW_WIDTH: u16 = 48;
+
+pub struct PlayerView<'a> {
+    pub title: &'a str,
+    pub artist: Option<&'a str>,
+    pub album: Option<&'a str>,
+    pub state: Option<PlayerState>,
+    pub shuffle_mode: ShuffleMode,
+    pub repeat_mode: RepeatMode,
+    pub timer: Option<PlaybackTimer>,
+    pub upcoming: &'a [AudioFile],
+    pub theme: &'a Theme,
+    pub cover_art: Option<&'a CoverArt>,
+    pub album_art: AlbumArtMode,
+}
+
+pub struct PlayerLayout {
+    // Used for click-to-seek.
+    pub progress_bar: Rect,
+    // Left blank for sixel or kitty output, which is written after the frame.
+    pub cover_art: Option<Rect>,
+}
+
+impl PlayerView<'_> {
+    pub fn render(&self, f: &mut Frame, area: Rect) -> PlayerLayout {
+        let (cover_art_area, area) = self.split_cover_art(area);
+        let cover_art = match (self.cover_art, cover_art_area) {
+            (Some(cover_art), Some(cover_art_area)) if !self.album_art.is_graphics() => {
+                f.render_widget(cover_art, cover_art_area);
+                None
+            }
+            _ => cover_art_area,
+        };
+
+        let narrow = area.width < NARROW_WIDTH;
+        let [info_area, progress_area, status_area, _, upcoming_area] = Layout::vertical([
+            Constraint::Length(if narrow { 2 } else { 3 }),
+            Constraint::Length(1),
+            Constraint::Length(if narrow { 2 } else { 1 }),
+            Constraint::Length(1),
+            Constraint::Min(0),
+        ])
+        .areas(area);
+
+        self.render_info(f, info_area, narrow);
+        let progress_bar = self.render_progress(f, progress_area, narrow);
+        self.render_status(f, status_area, narrow);
+        self.render_upcoming(f, upcoming_area);
+
+        PlayerLayout {
+            progress_bar,
+            cover_art,
+        }
+    }
+
+    // The art is kept roughly square, assuming cells twice as high as wide.
+    fn split_cover_art(&self, area: Rect) -> (Option<Rect>, Rect) {
+        if self.cover_art.is_none() || self.album_art == AlbumArtMode::Off || area.height < 6 {
+            return (None, area);
+        }
+        let width = (area.width / 3).min(area.height * 2);
+        if area.width.saturating_sub(width + 2) < NARROW_WIDTH {
+            return (None, area);
+        }
+
+        let [cover_art_area, rest] =
+            Layout::horizontal([Constraint::Length(width), Constraint::Min(0)])
+                .spacing(2)
+                .areas(area);
+        let cover_art_area = Rect {
+            height: width.div_ceil(2),
+            ..cover_art_area
+        };
+
+        (Some(cover_art_area), rest)
+    }
+
+    fn render_info(&self, f: &mut Frame, area: Rect, narrow: bool) {
+        let theme = self.theme;
+        let mut lines = vec![Line::styled(self.title, theme.current).bold()];
+        if let Some(artist) = self.artist {
+            lines.push(Line::styled(artist, theme.text));
+        }
+        if !narrow && let Some(album) = self.album {
+            lines.push(Line::styled(album, theme.dim).italic());
+        }
+
+        f.render_widget(Paragraph::new(lines), area);
+    }
+
+    fn render_progress(&self, f: &mut Frame, area: Rect, narrow: bool) -> Rect {
+        let (icon, position, duration) = match self.state {
+            Some(state) if state.ready => (
+                if state.paused { "⏸" } else { "▶" },
+                state.position(),
+                state.duration,
+            ),
+            _ => ("■", 0.0, 0.0),
+        };
+        let ratio = if duration > 0.0 {
+            (position / duration).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let label = if narrow {
+            secs_to_hms(position.floor() as u64)
+        } else {
+            format!(
+                "{} / {}",
+                secs_to_hms(position.floor() as u64),
+                secs_to_hms(duration.floor() as u64)
+            )
+        };
+
+        let [icon_area, gauge_area] =
+            Layout::horizontal([Constraint::Length(2), Constraint::Min(0)]).areas(area);
+        // LineGauge draws the label first and starts the line one column after it.
+        let line_start = (gauge_area.x + label.chars().count() as u16 + 1).min(gauge_area.right());
+        let progress_bar = Rect {
+            x: line_start,
+            width: gauge_area.right() - line_start,
+            ..gauge_area
+        };
+        f.render_widget(Paragraph::new(icon).style(self.theme.current), icon_area);
+        f.render_widget(
+            LineGauge::default()
+                .ratio(ratio)
+                .label(label)
+                .style(self.theme.text)
+                .filled_symbol("━")
+                .unfilled_symbol("─")
+                .filled_style(self.theme.progress)
+                .unfilled_style(self.theme.dim),
+            gauge_area,
+        );
+
+        progress_bar
+    }
+
+    fn render_status(&self, f: &mut Frame, area: Rect, narrow: bool) {
+        let theme = self.theme;
+        let volume = self.state.map_or(0, |state| state.volume);
+        let volume_gauge = LineGauge::default()
+            .ratio((volume.clamp(0, 100) as f64) / 100.0)
+            .label(format!("vol {volume:>3}"))
+            .style(theme.text)
+            .filled_symbol("━")
+            .unfilled_symbol("─")
+            .filled_style(theme.volume)
+            .unfilled_style(theme.dim);
+
+        let mut modes = vec![
+            Span::styled("shuffle: ", theme.dim),
+            Span::from(self.shuffle_mode.as_str()),
+            Span::styled("  repeat: ", theme.dim),
+            Span::from(self.repeat_mode.as_str()),
+        ];
+        if let Some(timer) = self.timer {
+            modes.push(Span::from("  "));
+            modes.push(Span::styled(timer.generate_str(), theme.timer));
+        }
+        let modes = Paragraph::new(Line::from(modes)).style(theme.text);
+
+        let [volume_area, modes_area] = if narrow {
+            Layout::vertical([Constraint::Length(1), Constraint::Length(1)]).areas(area)
+        } else {
+            Layout::horizontal([Constraint::Length(24), Constraint::Min(0)])
+                .spacing(2)
+                .areas(area)
+        };
+        f.render_widget(volume_gauge, volume_area);
+        f.render_widget(modes, modes_area);
+    }
+
+    fn render_upcoming(&self, f: &mut Frame, area: Rect) {
+        let mut lines = vec![Line::styled("Up next", self.theme.dim)];
+        if self.upcoming.is_empty() {
+            let next = match self.shuffle_mode {
+                ShuffleMode::Off => "nothing queued",
+                ShuffleMode::Random | ShuffleMode::Bag => "random track",
+            };
+            lines.push(Line::styled(format!("  {next}"), self.theme.text).italic());
+        }
+        for (i, audiofile) in self
+            .upcoming
+            .iter()
+            .take(area.height.saturating_sub(1).into())
+            .enumerate()
+        {
+            let mut entry = format!("{:>3}. ", i + 1);
+            entry.push_str(audiofile.title.as_deref().unwrap_or(&audiofile.path));
+            if let Some(ref artist) = audiofile.artist {
+                entry.push_str(" by ");
+                entry.push_str(artist);
+            }
+            lines.push(Line::styled(entry, self.theme.text));
+        }
+
+        f.render_widget(Paragraph::new(lines), area);
+    }
+}
+
+pub fn seek_position(
+    progress_bar: Rect,
+    state: Option<PlayerState>,
+    column: u16,
+    row: u16,
+) -> Option<f64> {
+    let state = state.filter(|state| state.ready && state.duration > 0.0)?;
+    if row != progress_bar.y || column < progress_bar.x || column >= progress_bar.right() {
+        return None;
+    }
+    let ratio = f64::from(column - progress_bar.x) / f64::from(progress_bar.width);
+
+    Some(ratio * state.duration)
+}
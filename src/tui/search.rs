@@ -0,0 +1,156 @@
+use crate::AudioFile;
+use crate::tui::follow_cursor;
+
+const MAX_RESULTS: usize = 500;
+
+pub struct Search {
+    // Lowercased path, title, artist and album of each audiofile, built once per library change.
+    haystacks: Vec<Vec<String>>,
+    results: Vec<usize>,
+    cursor: usize,
+    scroll: u16,
+}
+
+impl Search {
+    pub fn new() -> Self {
+        Search {
+            haystacks: vec![],
+            results: vec![],
+            cursor: 0,
+            scroll: 0,
+        }
+    }
+
+    pub fn set_audiofiles(&mut self, audiofiles: &[AudioFile]) {
+        self.haystacks = audiofiles
+            .iter()
+            .map(|audiofile| {
+                [
+                    Some(&audiofile.path),
+                    audiofile.title.as_ref(),
+                    audiofile.artist.as_ref(),
+                    audiofile.album.as_ref(),
+                ]
+                .into_iter()
+                .flatten()
+                .map(|field| field.chars().flat_map(char::to_lowercase).collect())
+                .collect()
+            })
+            .collect();
+    }
+
+    pub fn update(&mut self, query: &str) {
+        let words: Vec<Vec<char>> = query
+            .split_whitespace()
+            .map(|word| word.chars().flat_map(char::to_lowercase).collect())
+            .collect();
+        let mut results: Vec<(i64, usize)> = vec![];
+        if !words.is_empty() {
+            for (i, fields) in self.haystacks.iter().enumerate() {
+                if let Some(score) = score_fields(&words, fields) {
+                    results.push((score, i));
+                }
+            }
+        }
+        results.sort_by(|(a, _), (b, _)| b.cmp(a));
+        results.truncate(MAX_RESULTS);
+
+        self.results = results.into_iter().map(|(_, i)| i).collect();
+        self.cursor = 0;
+        self.scroll = 0;
+    }
+
+    pub fn move_cursor(&mut self, offset: i16) {
+        if offset > 0 {
+            self.cursor = (self.cursor + offset as usize).min(self.results.len().saturating_sub(1));
+        } else {
+            self.cursor = self.cursor.saturating_sub(offset.unsigned_abs() as usize);
+        }
+    }
+
+    pub fn selected_audiofiles(&self, audiofiles: &[AudioFile]) -> Vec<AudioFile> {
+        self.results
+            .get(self.cursor)
+            .and_then(|&i| audiofiles.get(i))
+            .map(|audiofile| vec![audiofile.clone()])
+            .unwrap_or_default()
+    }
+
+    pub fn generate_str(&self, audiofiles: &[AudioFile]) -> String {
+        let mut search_str = format!("{} results\n", self.results.len());
+        for (i, audiofile) in self
+            .results
+            .iter()
+            .filter_map(|&i| audiofiles.get(i))
+            .enumerate()
+        {
+            search_str.push_str(if i == self.cursor { "> " } else { "  " });
+            if let Some(ref title) = audiofile.title {
+                search_str.push_str(title);
+                if let Some(ref artist) = audiofile.artist {
+                    search_str.push_str(" by ");
+                    search_str.push_str(artist);
+                }
+                search_str.push_str(" - ");
+            }
+            search_str.push_str(&audiofile.path);
+            search_str.push('\n');
+        }
+
+        search_str
+    }
+
+    pub fn cursor_offset(&self, line: u16) -> Option<i16> {
+        let i = usize::from(self.scroll + line).checked_sub(1)?;
+
+        (i < self.results.len()).then(|| i as i16 - self.cursor as i16)
+    }
+
+    pub fn scroll(&mut self, height: u16) -> &mut u16 {
+        follow_cursor(&mut self.scroll, self.cursor as u16 + 1, height);
+
+        &mut self.scroll
+    }
+}
+
+fn score_fields(words: &[Vec<char>], fields: &[String]) -> Option<i64> {
+    let mut score = 0;
+    for word in words {
+        score += fields
+            .iter()
+            .filter_map(|field| fuzzy_match(word, field))
+            .max()?;
+    }
+
+    Some(score)
+}
+
+// Both the pattern and the text are expected to be lowercased already.
+fn fuzzy_match(pattern: &[char], text: &str) -> Option<i64> {
+    let mut text = text.chars();
+    let mut score = 0;
+    let mut prev: Option<char> = None;
+    let mut matched = false;
+    for &c in pattern {
+        let mut consecutive = matched;
+        loop {
+            let t = text.next()?;
+            if t == c {
+                break;
+            }
+            prev = Some(t);
+            consecutive = false;
+        }
+        score += 1;
+        if consecutive {
+            score += 5;
+        }
+        if prev.is_none_or(|prev| !prev.is_alphanumeric()) {
+            score += 3;
+        }
+        prev = Some(c);
+        matched = true;
+    }
+
+    Some(score)
+}
@@ -0,0 +1,195 @@
+use crate::SonicTunesError;
+use crate::config::ThemeConfig;
+use ratatui::style::{Color, Modifier, Style};
+
+const DEFAULT_THEME: &str = "default";
+const MONOCHROME_THEME: &str = "mono";
+
+#[derive(Clone)]
+pub struct Theme {
+    pub border: Style,
+    pub title: Style,
+    pub text: Style,
+    pub dim: Style,
+    pub highlight: Style,
+    pub current: Style,
+    pub progress: Style,
+    pub volume: Style,
+    pub command: Style,
+    pub error: Style,
+    pub timer: Style,
+}
+
+impl Theme {
+    fn default_theme() -> Self {
+        Theme {
+            border: Style::new(),
+            title: Style::new(),
+            text: Style::new(),
+            dim: Style::new().fg(Color::DarkGray),
+            highlight: Style::new().add_modifier(Modifier::BOLD),
+            current: Style::new().fg(Color::Cyan),
+            progress: Style::new().fg(Color::Cyan),
+            volume: Style::new().fg(Color::Green),
+            command: Style::new(),
+            error: Style::new().fg(Color::LightRed),
+            timer: Style::new().fg(Color::Yellow),
+        }
+    }
+
+    fn monochrome() -> Self {
+        Theme {
+            border: Style::new(),
+            title: Style::new().add_modifier(Modifier::BOLD),
+            text: Style::new(),
+            dim: Style::new().add_modifier(Modifier::DIM),
+            highlight: Style::new().add_modifier(Modifier::REVERSED),
+            current: Style::new().add_modifier(Modifier::BOLD),
+            progress: Style::new().add_modifier(Modifier::BOLD),
+            volume: Style::new().add_modifier(Modifier::BOLD),
+            command: Style::new(),
+            error: Style::new().add_modifier(Modifier::BOLD),
+            timer: Style::new(),
+        }
+    }
+
+    fn nord() -> Self {
+        Theme {
+            border: Style::new().fg(Color::Rgb(0x4c, 0x56, 0x6a)),
+            title: Style::new().fg(Color::Rgb(0x88, 0xc0, 0xd0)),
+            text: Style::new().fg(Color::Rgb(0xd8, 0xde, 0xe9)),
+            dim: Style::new().fg(Color::Rgb(0x61, 0x6e, 0x88)),
+            highlight: Style::new()
+                .fg(Color::Rgb(0xec, 0xef, 0xf4))
+                .bg(Color::Rgb(0x43, 0x4c, 0x5e)),
+            current: Style::new().fg(Color::Rgb(0x8f, 0xbc, 0xbb)),
+            progress: Style::new().fg(Color::Rgb(0x81, 0xa1, 0xc1)),
+            volume: Style::new().fg(Color::Rgb(0xa3, 0xbe, 0x8c)),
+            command: Style::new().fg(Color::Rgb(0xe5, 0xe9, 0xf0)),
+            error: Style::new().fg(Color::Rgb(0xbf, 0x61, 0x6a)),
+            timer: Style::new().fg(Color::Rgb(0xeb, 0xcb, 0x8b)),
+        }
+    }
+
+    fn gruvbox() -> Self {
+        Theme {
+            border: Style::new().fg(Color::Rgb(0x66, 0x5c, 0x54)),
+            title: Style::new().fg(Color::Rgb(0xfa, 0xbd, 0x2f)),
+            text: Style::new().fg(Color::Rgb(0xeb, 0xdb, 0xb2)),
+            dim: Style::new().fg(Color::Rgb(0x92, 0x83, 0x74)),
+            highlight: Style::new()
+                .fg(Color::Rgb(0xfb, 0xf1, 0xc7))
+                .bg(Color::Rgb(0x50, 0x49, 0x45)),
+            current: Style::new().fg(Color::Rgb(0x8e, 0xc0, 0x7c)),
+            progress: Style::new().fg(Color::Rgb(0xfe, 0x80, 0x19)),
+            volume: Style::new().fg(Color::Rgb(0xb8, 0xbb, 0x26)),
+            command: Style::new().fg(Color::Rgb(0xeb, 0xdb, 0xb2)),
+            error: Style::new().fg(Color::Rgb(0xfb, 0x49, 0x34)),
+            timer: Style::new().fg(Color::Rgb(0xd3, 0x86, 0x9b)),
+        }
+    }
+
+    fn from_config(
+        base: &Theme,
+        name: &str,
+        config: &ThemeConfig,
+    ) -> Result<Self, SonicTunesError> {
+        let color = |key: &str, value: &Option<String>, style: Style| match value {
+            Some(value) => value
+                .parse::<Color>()
+                .map(|color| style.fg(color))
+                .map_err(|_| {
+                    SonicTunesError::ConfigError(format!(
+                        "themes.{name}: invalid color `{value}` for `{key}`"
+                    ))
+                }),
+            None => Ok(style),
+        };
+
+        Ok(Theme {
+            border: color("border", &config.border, base.border)?,
+            title: color("title", &config.title, base.title)?,
+            text: color("text", &config.text, base.text)?,
+            dim: color("dim", &config.dim, base.dim)?,
+            highlight: color("highlight", &config.highlight, base.highlight)?,
+            current: color("current", &config.current, base.current)?,
+            progress: color("progress", &config.progress, base.progress)?,
+            volume: color("volume", &config.volume, base.volume)?,
+            command: color("command", &config.command, base.command)?,
+            error: color("error", &config.error, base.error)?,
+            timer: color("timer", &config.timer, base.timer)?,
+        })
+    }
+}
+
+pub struct Themes {
+    themes: std::collections::BTreeMap<String, Theme>,
+    current: Theme,
+}
+
+impl Themes {
+    pub fn new(
+        name: Option<&str>,
+        user_themes: &std::collections::BTreeMap<String, ThemeConfig>,
+    ) -> Result<Self, SonicTunesError> {
+        let mut themes = std::collections::BTreeMap::from([
+            (DEFAULT_THEME.to_string(), Theme::default_theme()),
+            (MONOCHROME_THEME.to_string(), Theme::monochrome()),
+            ("nord".to_string(), Theme::nord()),
+            ("gruvbox".to_string(), Theme::gruvbox()),
+        ]);
+        let builtin_themes = themes.clone();
+        for (theme_name, config) in user_themes {
+            // Anything else could not be typed after `:theme`.
+            if !theme_name
+                .chars()
+                .all(|c| c.is_alphanumeric() || ['-', '_', '.'].contains(&c))
+            {
+                return Err(SonicTunesError::ConfigError(format!(
+                    "themes.{theme_name}: theme names may only contain letters, digits, `-`, `_` and `.`"
+                )));
+            }
+            let base_name = config.base.as_deref().unwrap_or(DEFAULT_THEME);
+            let base = builtin_themes
+                .get(base_name)
+                .ok_or(SonicTunesError::ConfigError(format!(
+                    "themes.{theme_name}: unknown base theme `{base_name}`"
+                )))?;
+            let theme = Theme::from_config(base, theme_name, config)?;
+            themes.insert(theme_name.clone(), theme);
+        }
+
+        let name = name.unwrap_or(DEFAULT_THEME);
+        let current = themes
+            .get(name)
+            .ok_or(SonicTunesError::ConfigError(format!(
+                "theme: unknown theme `{name}`"
+            )))?
+            .clone();
+        // https://no-color.org
+        let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+
+        Ok(Themes {
+            current: if no_color {
+                Theme::monochrome()
+            } else {
+                current
+            },
+            themes,
+        })
+    }
+
+    pub fn current(&self) -> &Theme {
+        &self.current
+    }
+
+    pub fn select(&mut self, name: &str) -> bool {
+        match self.themes.get(name) {
+            Some(theme) => {
+                self.current = theme.clone();
+                true
+            }
+            None => false,
+        }
+    }
+}